@@ -1,5 +1,5 @@
 use clap::Parser;
-use tequila::{FromTequilaAttributes, TequilaRequest, TEQUILA_URL};
+use tequila::{attributes::Person, FromTequilaAttributes, TequilaRequest, TEQUILA_URL};
 use url::Url;
 
 #[derive(Parser, Debug)]
@@ -9,13 +9,6 @@ enum Args {
     Login { return_url: String },
 }
 
-#[derive(FromTequilaAttributes, Debug)]
-struct Attributes {
-    #[tequila("uniqueid")]
-    sciper: String,
-    username: String,
-}
-
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
@@ -26,27 +19,32 @@ async fn main() {
             let key = tequila::create_request(
                 Url::parse(&return_url).expect("Invalid url"),
                 "Tequila CLI example".into(),
-                vec!["uniqueid".into(), "username".into()],
-                Vec::new(),
+                Person::requested_attributes(),
+                Person::wished_attributes(),
+                None,
                 None,
                 None,
                 None,
             )
             .await
             .expect("Unable to fetch request key");
+            let key = key.expose();
             println!("Your request key is: {key}");
             println!("{TEQUILA_URL}/auth?requestkey={key}")
         }
         Args::FetchAttributes { key, auth_check } => {
             println!(
                 "{:#?}",
-                tequila::fetch_attributes::<Attributes>(key, auth_check)
-                    .await
-                    .expect("Could not fetch attributes")
+                tequila::fetch_attributes::<Person>(
+                    &key.parse().expect("Invalid request key"),
+                    &auth_check.parse().expect("Invalid auth_check"),
+                )
+                .await
+                .expect("Could not fetch attributes")
             )
         }
         Args::Login { return_url } => {
-            let req = TequilaRequest::new::<Attributes>(
+            let req = TequilaRequest::new::<Person>(
                 Url::parse(&return_url).expect("Invalid url"),
                 "Tequila CLI example".into(),
             )
@@ -54,7 +52,7 @@ async fn main() {
             .expect("Could not create request");
             println!(
                 "Login to {TEQUILA_URL}/auth?requestkey={} and input the auth_check",
-                req.key()
+                req.key().expose()
             );
 
             let mut auth_check = String::new();
@@ -63,14 +61,15 @@ async fn main() {
                 .expect("Could not read from stdin");
 
             let req = req
-                .fetch_attributes(auth_check)
+                .fetch_attributes(&auth_check.trim().parse().expect("Invalid auth_check"))
                 .await
+                .map_err(|(e, _)| e)
                 .expect("Could not fetch attributes");
 
             println!(
                 "Hi, {} ({})",
-                req.attributes().username,
-                req.attributes().sciper
+                req.attributes().display_name(),
+                req.attributes().id()
             )
         }
     }
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+/// The attributes received from Tequila. Unlike a `HashMap`, it keeps all the values of a key if it is received several times, as well as the order in which they were received
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttributeMap {
+    entries: Vec<(String, String)>,
+}
+
+impl AttributeMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value to a key, after the ones it may already have
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((key.into(), value.into()))
+    }
+
+    /// Get the first value of a key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get all the values of a key, in the order they were received
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    /// Removes all the values of a key, returning them
+    pub fn remove(&mut self, key: &str) -> Vec<String> {
+        let (removed, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(k, _)| k == key);
+        self.entries = kept;
+        removed.into_iter().map(|(_, v)| v).collect()
+    }
+
+    /// The distinct keys, in the order they were first received
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(i, (k, _))| !self.entries[..*i].iter().any(|(l, _)| l == k))
+            .map(|(_, (k, _))| k.as_str())
+    }
+
+    /// All the key value pairs, in the order they were received
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The number of key value pairs
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl FromIterator<(String, String)> for AttributeMap {
+    fn from_iter<T: IntoIterator<Item = (String, String)>>(iter: T) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a str)> for AttributeMap {
+    fn from_iter<T: IntoIterator<Item = (&'a str, &'a str)>>(iter: T) -> Self {
+        iter.into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+}
+
+impl Extend<(String, String)> for AttributeMap {
+    fn extend<T: IntoIterator<Item = (String, String)>>(&mut self, iter: T) {
+        self.entries.extend(iter)
+    }
+}
+
+impl IntoIterator for AttributeMap {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl From<HashMap<String, String>> for AttributeMap {
+    fn from(map: HashMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> AttributeMap {
+        AttributeMap::from_iter([("group", "a"), ("name", "n"), ("group", "b"), ("unit", "u")])
+    }
+
+    #[test]
+    fn get_returns_the_first_value() {
+        assert_eq!(map().get("group"), Some("a"));
+        assert_eq!(map().get("missing"), None);
+    }
+
+    #[test]
+    fn get_all_keeps_the_order() {
+        assert_eq!(map().get_all("group").collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(map().get_all("missing").count(), 0);
+    }
+
+    #[test]
+    fn keys_are_distinct() {
+        assert_eq!(map().keys().collect::<Vec<_>>(), ["group", "name", "unit"]);
+        assert_eq!(map().len(), 4);
+    }
+
+    #[test]
+    fn remove_takes_every_value() {
+        let mut map = map();
+
+        assert_eq!(map.remove("group"), ["a", "b"]);
+        assert!(!map.contains_key("group"));
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [("name", "n"), ("unit", "u")]
+        );
+        assert!(map.remove("group").is_empty());
+    }
+}
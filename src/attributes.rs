@@ -0,0 +1,411 @@
+//! Ready-made attribute types for the attributes commonly requested to EPFL's Tequila server. They are built with the derive macros, and can be flattened into your own types
+//! ```
+//! use tequila::{attributes::Identity, FromTequilaAttributes};
+//!
+//! #[derive(FromTequilaAttributes)]
+//! #[tequila(no_check)]
+//! struct MyAttributes {
+//!     #[tequila(flatten)]
+//!     identity: Identity,
+//!     phone: Option<String>,
+//! }
+//! ```
+
+use std::{collections::BTreeSet, convert::Infallible, fmt, str::FromStr};
+
+use crate::{FromTequilaAttributes, IntoTequilaAttributes};
+
+/// A SCIPER, the 6 digits number identifying a person at EPFL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sciper(u32);
+
+/// Error returned when parsing a [Sciper] from a string which is not made of 6 digits
+#[derive(Debug)]
+pub struct InvalidSciper(pub String);
+
+impl Sciper {
+    /// Creates a SCIPER, checking that it has 6 digits
+    pub fn new(sciper: u32) -> Result<Self, InvalidSciper> {
+        if (100_000..=999_999).contains(&sciper) {
+            Ok(Self(sciper))
+        } else {
+            Err(InvalidSciper(sciper.to_string()))
+        }
+    }
+
+    pub fn get(&self) -> u32 {
+        self.0
+    }
+}
+
+impl FromStr for Sciper {
+    type Err = InvalidSciper;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 6 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(InvalidSciper(s.into()));
+        }
+        s.parse()
+            .map_err(|_| InvalidSciper(s.into()))
+            .and_then(Self::new)
+    }
+}
+
+impl fmt::Display for Sciper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The value of the `uniqueid` attribute, identifying a user: the SCIPER of a person, or the identifier of a guest account
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UniqueId {
+    Sciper(Sciper),
+    /// A guest account, which has no SCIPER. Its identifier is a `G` followed by digits, such as `G12345`
+    Guest(String),
+}
+
+/// Error returned when parsing a [UniqueId] from a string which is neither a SCIPER nor a guest identifier
+#[derive(Debug)]
+pub struct InvalidUniqueId(pub String);
+
+impl UniqueId {
+    /// The SCIPER of the user, unless it is a guest
+    pub fn as_sciper(&self) -> Option<Sciper> {
+        match self {
+            UniqueId::Sciper(sciper) => Some(*sciper),
+            UniqueId::Guest(_) => None,
+        }
+    }
+
+    pub fn is_guest(&self) -> bool {
+        matches!(self, UniqueId::Guest(_))
+    }
+}
+
+impl FromStr for UniqueId {
+    type Err = InvalidUniqueId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(sciper) = s.parse() {
+            return Ok(UniqueId::Sciper(sciper));
+        }
+        match s.strip_prefix('G') {
+            Some(id) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) => {
+                Ok(UniqueId::Guest(s.into()))
+            }
+            _ => Err(InvalidUniqueId(s.into())),
+        }
+    }
+}
+
+impl fmt::Display for UniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniqueId::Sciper(sciper) => sciper.fmt(f),
+            UniqueId::Guest(id) => f.write_str(id),
+        }
+    }
+}
+
+/// The groups a user is a member of, received as a comma separated list in the `group` attribute
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Groups(BTreeSet<String>);
+
+impl Groups {
+    pub fn contains(&self, group: &str) -> bool {
+        self.0.contains(group)
+    }
+
+    /// Whether the user is a member of at least one of the groups
+    pub fn any_of<'a>(&self, groups: impl IntoIterator<Item = &'a str>) -> bool {
+        groups.into_iter().any(|g| self.contains(g))
+    }
+
+    /// Whether the user is a member of all the groups
+    pub fn all_of<'a>(&self, groups: impl IntoIterator<Item = &'a str>) -> bool {
+        groups.into_iter().all(|g| self.contains(g))
+    }
+
+    /// The groups, in alphabetical order
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Groups {
+    type Item = &'a str;
+    type IntoIter =
+        std::iter::Map<std::collections::btree_set::Iter<'a, String>, fn(&String) -> &str>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+impl<S: Into<String>> FromIterator<S> for Groups {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(Into::into)
+                .filter(|g: &String| !g.is_empty())
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for Groups {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.split(',').map(str::trim).collect())
+    }
+}
+
+impl fmt::Display for Groups {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.iter().collect::<Vec<_>>().join(","))
+    }
+}
+
+/// An organizational unit of EPFL, with its position in the hierarchy. It is written from the unit up to the root, separated by `/`, as in the `where` attribute: `LASIG/IIE/ENAC/EPFL`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Unit {
+    /// The names of the units, from the root down to this one
+    path: Vec<String>,
+}
+
+/// Error returned when parsing a [Unit] from a string with an empty segment
+#[derive(Debug)]
+pub struct InvalidUnit(pub String);
+
+impl Unit {
+    /// The name of the unit itself
+    pub fn name(&self) -> &str {
+        self.path.last().unwrap()
+    }
+
+    /// The unit containing this one, if it is not the root
+    pub fn parent(&self) -> Option<Unit> {
+        (self.path.len() > 1).then(|| Unit {
+            path: self.path[..self.path.len() - 1].to_vec(),
+        })
+    }
+
+    /// The names of the units containing this one, from its parent up to the root
+    pub fn ancestors(&self) -> impl Iterator<Item = &str> {
+        self.path.iter().rev().skip(1).map(String::as_str)
+    }
+
+    /// The number of units above this one
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    /// Whether this unit is the one named `name`, or is anywhere under it
+    pub fn is_within(&self, name: &str) -> bool {
+        self.path.iter().any(|u| u == name)
+    }
+
+    /// Whether `other` is strictly under this unit
+    pub fn is_ancestor_of(&self, other: &Unit) -> bool {
+        other.path.len() > self.path.len() && other.path.starts_with(&self.path)
+    }
+
+    /// Whether this unit is strictly under `other`
+    pub fn is_descendant_of(&self, other: &Unit) -> bool {
+        other.is_ancestor_of(self)
+    }
+}
+
+impl FromStr for Unit {
+    type Err = InvalidUnit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = s.split('/').rev().map(str::trim).collect::<Vec<_>>();
+        if path.iter().any(|u| u.is_empty()) {
+            return Err(InvalidUnit(s.into()));
+        }
+        Ok(Self {
+            path: path.into_iter().map(String::from).collect(),
+        })
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            &self
+                .path
+                .iter()
+                .rev()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("/"),
+        )
+    }
+}
+
+/// Who the user is: their identifier and their names. Guests are accepted, see [sciper](Self::sciper) for the users which have a SCIPER
+#[derive(FromTequilaAttributes, IntoTequilaAttributes, Clone, PartialEq, Eq)]
+#[tequila(no_check)]
+pub struct Identity {
+    #[tequila("uniqueid", sensitive)]
+    pub id: UniqueId,
+    /// The family name
+    pub name: String,
+    pub firstname: String,
+}
+
+impl Identity {
+    /// The first name followed by the family name, as displayed at EPFL
+    pub fn display_name(&self) -> String {
+        format!("{} {}", self.firstname, self.name)
+    }
+
+    /// The SCIPER of the user, unless it is a guest
+    pub fn sciper(&self) -> Option<Sciper> {
+        self.id.as_sciper()
+    }
+}
+
+/// The attributes usually needed by an application: the user's identity, contact and affiliations
+#[derive(FromTequilaAttributes, IntoTequilaAttributes, Clone, PartialEq, Eq)]
+#[tequila(no_check)]
+pub struct Person {
+    #[tequila(flatten)]
+    pub identity: Identity,
+    #[tequila(sensitive)]
+    pub email: Option<String>,
+    /// The GASPAR username
+    pub username: Option<String>,
+    /// The names of the units the user belongs to
+    pub unit: Vec<String>,
+    /// The groups the user is a member of, empty if the `group` attribute was not received
+    #[tequila(collect)]
+    pub group: Groups,
+    /// The units the user belongs to, with their position in the hierarchy
+    #[tequila("where")]
+    pub units: Vec<Unit>,
+}
+
+impl Person {
+    /// See [Identity::display_name]
+    pub fn display_name(&self) -> String {
+        self.identity.display_name()
+    }
+
+    pub fn id(&self) -> &UniqueId {
+        &self.identity.id
+    }
+
+    /// See [Identity::sciper]
+    pub fn sciper(&self) -> Option<Sciper> {
+        self.identity.sciper()
+    }
+
+    /// Whether one of the user's units is `name` or is anywhere under it, such as `person.is_within("ENAC")`
+    pub fn is_within(&self, name: &str) -> bool {
+        self.units.iter().any(|u| u.is_within(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sciper_has_six_digits() {
+        assert_eq!("123456".parse::<Sciper>().unwrap().get(), 123456);
+        assert_eq!(Sciper::new(999999).unwrap().to_string(), "999999");
+
+        for sciper in [
+            "", "12345", "1234567", "012345", "+12345", "12345a", " 123456",
+        ] {
+            assert!(sciper.parse::<Sciper>().is_err(), "{sciper:?}");
+        }
+        assert!(Sciper::new(99999).is_err());
+        assert!(Sciper::new(1_000_000).is_err());
+    }
+
+    #[test]
+    fn unique_id_accepts_guests() {
+        let sciper = "123456".parse::<UniqueId>().unwrap();
+        assert_eq!(sciper.as_sciper(), Some(Sciper::new(123456).unwrap()));
+        assert!(!sciper.is_guest());
+
+        let guest = "G12345".parse::<UniqueId>().unwrap();
+        assert_eq!(guest, UniqueId::Guest("G12345".into()));
+        assert_eq!(guest.as_sciper(), None);
+        assert_eq!(guest.to_string(), "G12345");
+
+        for id in ["", "G", "G12a", "g12345", "12345", "X12345"] {
+            assert!(id.parse::<UniqueId>().is_err(), "{id:?}");
+        }
+    }
+
+    fn unit(s: &str) -> Unit {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn unit_is_parsed_from_the_leaf_up() {
+        let lasig = unit("LASIG/IIE/ENAC/EPFL");
+
+        assert_eq!(lasig.name(), "LASIG");
+        assert_eq!(lasig.depth(), 3);
+        assert_eq!(
+            lasig.ancestors().collect::<Vec<_>>(),
+            ["IIE", "ENAC", "EPFL"]
+        );
+        assert_eq!(lasig.to_string(), "LASIG/IIE/ENAC/EPFL");
+        assert_eq!(unit(" IIE / ENAC/EPFL "), unit("IIE/ENAC/EPFL"));
+
+        let epfl = unit("EPFL");
+        assert_eq!(epfl.depth(), 0);
+        assert_eq!(epfl.parent(), None);
+        assert_eq!(lasig.parent(), Some(unit("IIE/ENAC/EPFL")));
+    }
+
+    #[test]
+    fn unit_rejects_empty_segments() {
+        for s in ["", "/", "LASIG//ENAC", "/EPFL", "LASIG/", " /EPFL"] {
+            assert!(
+                matches!(s.parse::<Unit>(), Err(InvalidUnit(u)) if u == s),
+                "{s:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unit_hierarchy_queries() {
+        let epfl = unit("EPFL");
+        let enac = unit("ENAC/EPFL");
+        let lasig = unit("LASIG/IIE/ENAC/EPFL");
+        let ic = unit("IC/EPFL");
+
+        assert!(epfl.is_ancestor_of(&lasig));
+        assert!(enac.is_ancestor_of(&lasig));
+        assert!(!enac.is_ancestor_of(&enac));
+        assert!(!ic.is_ancestor_of(&lasig));
+        assert!(!lasig.is_ancestor_of(&enac));
+        // A unit with the same name elsewhere in the hierarchy is another unit
+        assert!(!enac.is_ancestor_of(&unit("LASIG/ENAC/OTHER")));
+
+        assert!(lasig.is_descendant_of(&enac));
+        assert!(!enac.is_descendant_of(&lasig));
+        assert!(!lasig.is_descendant_of(&lasig));
+
+        assert!(lasig.is_within("LASIG"));
+        assert!(lasig.is_within("ENAC"));
+        assert!(!lasig.is_within("IC"));
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+use crate::{
+    metrics, parser, serializer, AttributeMap, AuthCheck, FromTequilaAttributes, RequestKey,
+    TequilaError, TEQUILA_URL,
+};
+
+/// The time given to establish a connection with Tequila's servers by [TequilaClient::new]
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The time given to a whole call by [TequilaClient::new], from connecting to reading the end of the response
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+static SHARED: OnceLock<TequilaClient> = OnceLock::new();
+
+/// Client making the calls to Tequila's API, with their timeouts. Cloning it is cheap, since the clones share their connections.
+///
+/// The underlying HTTP client has no timeout for individual reads, so the timeout of the whole call stands in for it: a server which stops sending data fails the call once it is exceeded.
+///
+/// No task is spawned for a call: dropping its future before it completes cancels it and closes the connection
+#[derive(Debug, Clone)]
+pub struct TequilaClient {
+    http: reqwest::Client,
+    timeout: Duration,
+}
+
+impl Default for TequilaClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TequilaClient {
+    /// Creates a client with [DEFAULT_CONNECT_TIMEOUT] and [DEFAULT_TIMEOUT]
+    /// # Panics
+    /// Like `reqwest::Client::new`, if the TLS backend cannot be initialized
+    pub fn new() -> Self {
+        Self::with_timeouts(DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT)
+    }
+
+    /// Creates a client giving up after `connect_timeout` when establishing a connection, and after `timeout` for a whole call. Fails with [TequilaError::Timeout] when one of them is exceeded
+    /// # Panics
+    /// Like `reqwest::Client::new`, if the TLS backend cannot be initialized
+    pub fn with_timeouts(connect_timeout: Duration, timeout: Duration) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .connect_timeout(connect_timeout)
+                .build()
+                .expect("Could not create the HTTP client"),
+            timeout,
+        }
+    }
+
+    /// Returns a client sharing the connections of this one, whose calls time out after `timeout`. Use it to change the timeout of a single call.
+    ///
+    /// Only the timeout of the whole call is changed: the connect timeout is set on the shared connections, so it stays the one of this client. Use [with_timeouts](Self::with_timeouts) for a client with another connect timeout
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        Self {
+            http: self.http.clone(),
+            timeout,
+        }
+    }
+
+    /// The client used by the free functions and by default by [TequilaRequest](crate::TequilaRequest). Unless [set_shared](Self::set_shared) was called, it is created with [new](Self::new) on first use
+    pub fn shared() -> &'static Self {
+        SHARED.get_or_init(Self::new)
+    }
+
+    /// Replaces the shared client, for instance to change its timeouts. It must be called before the shared client is first used, otherwise the client given is returned
+    pub fn set_shared(client: TequilaClient) -> Result<(), TequilaClient> {
+        SHARED.set(client)
+    }
+
+    /// Same as [create_request](crate::create_request), using this client
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                service = %service_name,
+                requested = request_attributes.len(),
+                wished = wish_attributes.len(),
+            )
+        )
+    )]
+    pub async fn create_request(
+        &self,
+        return_url: Url,
+        service_name: String,
+        request_attributes: Vec<String>,
+        wish_attributes: Vec<String>,
+        require: Option<String>,
+        allow: Option<String>,
+        language: Option<String>,
+        authstrength: Option<u32>,
+    ) -> Result<RequestKey, TequilaError> {
+        let key = self
+            .send_request::<CreateRequestResponse>("createrequest".into(), {
+                let mut vec = vec![
+                    ("urlaccess", return_url.to_string()),
+                    ("service", service_name),
+                    ("mode_auth_check", "1".into()),
+                    ("charset", "utf-8".into()),
+                ];
+
+                if !request_attributes.is_empty() {
+                    vec.push(("request", request_attributes.join(",")))
+                }
+                if !wish_attributes.is_empty() {
+                    vec.push(("wish", wish_attributes.join(",")))
+                }
+                if let Some(require) = require {
+                    vec.push(("require", require))
+                }
+                if let Some(allow) = allow {
+                    vec.push(("allow", allow))
+                }
+                if let Some(language) = language {
+                    vec.push(("language", language))
+                }
+                if let Some(authstrength) = authstrength {
+                    vec.push(("authstrength", authstrength.to_string()))
+                }
+
+                vec
+            })
+            .await
+            .map(|response| response.key);
+
+        metrics::notify(|m| match &key {
+            Ok(_) => m.request_created(),
+            Err(e) => m.error(e),
+        });
+        key
+    }
+
+    /// Same as [fetch_attributes](crate::fetch_attributes), using this client
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub async fn fetch_attributes<A>(
+        &self,
+        key: &RequestKey,
+        auth_check: &AuthCheck,
+    ) -> Result<A, TequilaError>
+    where
+        A: FromTequilaAttributes,
+    {
+        let attributes = self
+            .fetch(key, auth_check)
+            .await
+            .and_then(A::from_tequila_attributes);
+        notify_login(&attributes);
+        attributes
+    }
+
+    /// Calls the `"fetchattributes"` route, leaving the conversion of the attributes to the caller
+    pub(crate) async fn fetch(
+        &self,
+        key: &RequestKey,
+        auth_check: &AuthCheck,
+    ) -> Result<AttributeMap, TequilaError> {
+        self.send_request(
+            "fetchattributes".into(),
+            vec![
+                ("key", key.expose().into()),
+                ("auth_check", auth_check.expose().into()),
+            ],
+        )
+        .await
+    }
+
+    /// Send a request to the API
+    /// # Parameters:
+    /// - route: the route to call (only the uri of the method, like `"createrequest"`, not the full url)
+    /// - body: a list of key value pairs
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(route = %route, status, latency_ms))
+    )]
+    async fn send_request<R>(
+        &self,
+        route: String,
+        body: Vec<(&str, String)>,
+    ) -> Result<R, TequilaError>
+    where
+        R: FromTequilaAttributes,
+    {
+        let body = serializer::serialize(&body)?;
+        let start = Instant::now();
+
+        let attributes = async {
+            let response = self
+                .http
+                .post(format!("{TEQUILA_URL}/{route}"))
+                .timeout(self.timeout)
+                .header("Content-Type", "text/plain; charset=utf-8")
+                .body(body)
+                .send()
+                .await
+                .map_err(request_error)?;
+
+            #[cfg(feature = "tracing")]
+            tracing::Span::current().record("status", response.status().as_u16());
+
+            // The body is decoded manually, since Tequila may answer in latin-1 without declaring it
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+            let body = response.bytes().await.map_err(request_error)?;
+
+            parser::parse(&parser::decode(&body, content_type.as_deref()))
+        }
+        .await;
+        metrics::notify(|m| m.latency(&route, start.elapsed()));
+
+        // Only the number of attributes and the kind of the error are recorded, since they may contain personal data
+        #[cfg(feature = "tracing")]
+        {
+            tracing::Span::current().record("latency_ms", start.elapsed().as_millis() as u64);
+            match &attributes {
+                Ok(attributes) => {
+                    tracing::debug!(attributes = attributes.len(), "response received")
+                }
+                Err(e) => tracing::warn!(error = e.kind(), "request failed"),
+            }
+        }
+
+        R::from_tequila_attributes(attributes?)
+    }
+}
+
+/// Separates the timeouts from the other network errors
+fn request_error(e: reqwest::Error) -> TequilaError {
+    if e.is_timeout() {
+        TequilaError::Timeout
+    } else {
+        TequilaError::RequestError(e)
+    }
+}
+
+/// Notifies the metrics of the outcome of a login
+pub(crate) fn notify_login<A>(attributes: &Result<A, TequilaError>) {
+    metrics::notify(|m| match attributes {
+        Ok(_) => m.login_completed(),
+        Err(e) => m.error(e),
+    });
+}
+
+impl FromTequilaAttributes for CreateRequestResponse {
+    fn from_tequila_attributes(attributes: AttributeMap) -> Result<Self, TequilaError> {
+        Ok(Self {
+            key: attributes
+                .get("key")
+                .and_then(|key| key.parse().ok())
+                .ok_or(TequilaError::InvalidResponse)?,
+        })
+    }
+
+    fn wished_attributes() -> Vec<String> {
+        vec![]
+    }
+
+    fn requested_attributes() -> Vec<String> {
+        vec![]
+    }
+}
+
+/// The response from the `"create_request"` route
+struct CreateRequestResponse {
+    key: RequestKey,
+}
@@ -0,0 +1,144 @@
+use crate::{AttributeMap, FromTequilaAttributes, IntoTequilaAttributes, TequilaError};
+
+/// Attributes whose requested and wished lists are only known at runtime, for instance when they are read from a configuration file.
+///
+/// Since [FromTequilaAttributes::requested_attributes] and [FromTequilaAttributes::wished_attributes] cannot know these lists, they are empty: the request must be created with [TequilaRequest::with_instance](crate::TequilaRequest::with_instance), using an instance built with [DynamicAttributes::new] as a template
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DynamicAttributes {
+    requested: Vec<String>,
+    wished: Vec<String>,
+    values: AttributeMap,
+}
+
+impl DynamicAttributes {
+    /// Creates a template requesting the attributes in `requested`, and wishing the ones in `wished`
+    pub fn new(requested: Vec<String>, wished: Vec<String>) -> Self {
+        Self {
+            requested,
+            wished,
+            values: AttributeMap::new(),
+        }
+    }
+
+    /// The attributes requested
+    pub fn requested(&self) -> &[String] {
+        &self.requested
+    }
+
+    /// The attributes wished
+    pub fn wished(&self) -> &[String] {
+        &self.wished
+    }
+
+    /// Get the value of an attribute, if it was received
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key)
+    }
+
+    /// All the attributes received
+    pub fn values(&self) -> &AttributeMap {
+        &self.values
+    }
+}
+
+impl FromTequilaAttributes for DynamicAttributes {
+    fn from_tequila_attributes(attributes: AttributeMap) -> Result<Self, TequilaError> {
+        Ok(Self {
+            requested: vec![],
+            wished: vec![],
+            values: attributes,
+        })
+    }
+
+    fn wished_attributes() -> Vec<String> {
+        vec![]
+    }
+
+    fn requested_attributes() -> Vec<String> {
+        vec![]
+    }
+
+    fn instance_requested_attributes(&self) -> Vec<String> {
+        self.requested.clone()
+    }
+
+    fn instance_wished_attributes(&self) -> Vec<String> {
+        self.wished.clone()
+    }
+
+    fn instance_from_tequila_attributes(
+        &self,
+        attributes: AttributeMap,
+    ) -> Result<Self, TequilaError> {
+        let missing = self
+            .requested
+            .iter()
+            .filter(|a| !attributes.contains_key(a))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(TequilaError::MissingAttributes(missing));
+        }
+
+        Ok(Self {
+            requested: self.requested.clone(),
+            wished: self.wished.clone(),
+            values: attributes,
+        })
+    }
+}
+
+impl IntoTequilaAttributes for DynamicAttributes {
+    fn into_tequila_attributes(self) -> AttributeMap {
+        self.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> DynamicAttributes {
+        DynamicAttributes::new(vec!["uniqueid".into(), "name".into()], vec!["email".into()])
+    }
+
+    #[test]
+    fn instance_lists_are_the_configured_ones() {
+        let template = template();
+
+        assert_eq!(
+            template.instance_requested_attributes(),
+            ["uniqueid", "name"]
+        );
+        assert_eq!(template.instance_wished_attributes(), ["email"]);
+        assert!(DynamicAttributes::requested_attributes().is_empty());
+        assert!(DynamicAttributes::wished_attributes().is_empty());
+    }
+
+    #[test]
+    fn instance_keeps_the_values_received() {
+        let attributes = template()
+            .instance_from_tequila_attributes(AttributeMap::from_iter([
+                ("uniqueid", "123456"),
+                ("name", "Doe"),
+                ("other", "o"),
+            ]))
+            .unwrap();
+
+        assert_eq!(attributes.get("name"), Some("Doe"));
+        assert_eq!(attributes.get("other"), Some("o"));
+        assert_eq!(attributes.get("email"), None);
+        assert_eq!(attributes.requested(), ["uniqueid", "name"]);
+        assert_eq!(attributes.wished(), ["email"]);
+    }
+
+    #[test]
+    fn instance_reports_missing_requested_attributes() {
+        assert!(matches!(
+            template().instance_from_tequila_attributes(AttributeMap::from_iter([("email", "e")])),
+            Err(TequilaError::MissingAttributes(m)) if m == ["uniqueid", "name"]
+        ));
+        // Without a template, nothing is requested
+        assert!(DynamicAttributes::from_tequila_attributes(AttributeMap::new()).is_ok());
+    }
+}
@@ -2,7 +2,7 @@
 //! 
 //! Here is a quick reminder of Tequila's authentification flow:
 //! - First, a request must be created on Tequila's servers, with the list of attributes you want to get about the user, requirements, etc, and a url where the user will be redirected. 
-//! The server will return an API key, and the user must connect to `TEQUILA_URL/auth?requestkey={key}`
+//!   The server will return an API key, and the user must connect to `TEQUILA_URL/auth?requestkey={key}`
 //! - After successful login, the user will be redirected to `{return_url}?key={key}&authcheck={auth_check}`
 //! - To get the requested attributes, a second call must be made, using the request key (key) and the authentification token (auth_check)
 //! 
@@ -14,27 +14,122 @@
 //! # Direct calls
 //! If you do not wish to use [TequilaRequest], you can make direct calls to the API through the functions [create_request] and [fetch_attributes].
 
-use std::{collections::HashMap, marker::PhantomData};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
 
 use url::Url;
 
+// Allows the types of the crate to use the derive macros, which refer to `::tequila`
+extern crate self as tequila;
+
+pub use attribute_map::AttributeMap;
+pub use client::{TequilaClient, DEFAULT_CONNECT_TIMEOUT, DEFAULT_TIMEOUT};
+pub use dynamic::DynamicAttributes;
+pub use metrics::{set_metrics, Metrics};
+#[cfg(feature = "pool")]
+pub use pool::KeyPool;
+pub use redacted::Redacted;
+pub use retry::RetryPolicy;
 pub use tequila_macros::*;
+pub use token::{AuthCheck, InvalidToken, RequestKey};
+
+mod attribute_map;
+pub mod attributes;
+mod client;
+mod dynamic;
+mod metrics;
+mod parser;
+#[cfg(feature = "pool")]
+mod pool;
+mod redacted;
+mod retry;
+pub mod roles;
+mod serializer;
+mod token;
 
 pub const TEQUILA_URL: &str = "https://tequila.epfl.ch/cgi-bin/tequila";
 
+/// Keys that Tequila adds to the response of `fetchattributes`, regardless of the requested attributes. They are never considered as unknown attributes
+pub const PROTOCOL_KEYS: [&str; 8] = [
+    "key",
+    "status",
+    "version",
+    "host",
+    "org",
+    "authstrength",
+    "authorig",
+    "requesthost",
+];
+
 /// This trait allow an object to be constructed from the response of the `fetch_attributes` route. It should be derived with the [FromTequilaAttributes](tequila_macros::FromTequilaAttributes) macro
 pub trait FromTequilaAttributes
 where
     Self: Sized,
 {
-    fn from_tequila_attributes(attributes: HashMap<String, String>) -> Result<Self, TequilaError>;
+    fn from_tequila_attributes(attributes: AttributeMap) -> Result<Self, TequilaError>;
 
     fn wished_attributes() -> Vec<String>;
     fn requested_attributes() -> Vec<String>;
+
+    /// Constructs an object from the attributes of a type it is flattened into. `known` holds the attributes used by that type, which this one must not consider unknown (see `#[tequila(deny_unknown)]` and `#[tequila(rest)]`).
+    /// Defaults to [from_tequila_attributes](Self::from_tequila_attributes)
+    fn from_flattened_attributes(
+        attributes: AttributeMap,
+        known: &[String],
+    ) -> Result<Self, TequilaError> {
+        let _ = known;
+        Self::from_tequila_attributes(attributes)
+    }
+
+    /// The filter imposed on the user's attributes, see the `require` parameter of [create_request]
+    fn require() -> Option<String> {
+        None
+    }
+
+    /// The restrictions lifted on the user's attributes, see the `allow` parameter of [create_request]
+    fn allow() -> Option<String> {
+        None
+    }
+
+    /// The name of the service this type belongs to, see the `service_name` parameter of [create_request]
+    fn service() -> Option<String> {
+        None
+    }
+
+    /// The language of the login window, see the `language` parameter of [create_request]
+    fn language() -> Option<String> {
+        None
+    }
+
+    /// The minimal strength of the authentification, see the `authstrength` parameter of [create_request]
+    fn authstrength() -> Option<u32> {
+        None
+    }
+
+    /// The attributes requested by this instance, when it is used as a template to create a request (see [TequilaRequest::with_instance]). Defaults to [requested_attributes](Self::requested_attributes)
+    fn instance_requested_attributes(&self) -> Vec<String> {
+        Self::requested_attributes()
+    }
+
+    /// The attributes wished by this instance, when it is used as a template to create a request (see [TequilaRequest::with_instance]). Defaults to [wished_attributes](Self::wished_attributes)
+    fn instance_wished_attributes(&self) -> Vec<String> {
+        Self::wished_attributes()
+    }
+
+    /// Constructs an object from the response, using this instance as a template (see [TequilaRequest::with_instance]). Defaults to [from_tequila_attributes](Self::from_tequila_attributes)
+    fn instance_from_tequila_attributes(
+        &self,
+        attributes: AttributeMap,
+    ) -> Result<Self, TequilaError> {
+        Self::from_tequila_attributes(attributes)
+    }
 }
 
 impl FromTequilaAttributes for () {
-    fn from_tequila_attributes(_: HashMap<String, String>) -> Result<Self, TequilaError> {
+    fn from_tequila_attributes(_: AttributeMap) -> Result<Self, TequilaError> {
         Ok(())
     }
 
@@ -47,19 +142,69 @@ impl FromTequilaAttributes for () {
     }
 }
 
-/// Constructs a hashmap from a response from Tequila's API. The string is composed of key value pairs using `=` as a bind, and delimited by line feeds
-fn build_hashmap(str: String) -> Result<HashMap<String, String>, TequilaError> {
-    str.split('\n')
-        .filter(|s| !s.is_empty())
-        .map(|s| s.split_once('='))
-        .fold(Ok(HashMap::new()), |map, opt| match (map, opt) {
-            (Ok(mut map), Some((key, value))) => {
-                map.insert(key.into(), value.into());
-                Ok(map)
-            }
-            (Err(e), _) => Err(e),
-            (_, None) => Err(TequilaError::InvalidResponse),
-        })
+impl FromTequilaAttributes for AttributeMap {
+    fn from_tequila_attributes(attributes: AttributeMap) -> Result<Self, TequilaError> {
+        Ok(attributes)
+    }
+
+    fn wished_attributes() -> Vec<String> {
+        vec![]
+    }
+
+    fn requested_attributes() -> Vec<String> {
+        vec![]
+    }
+}
+
+/// When a key is received several times, only its last value is kept
+impl FromTequilaAttributes for HashMap<String, String> {
+    fn from_tequila_attributes(attributes: AttributeMap) -> Result<Self, TequilaError> {
+        Ok(attributes.into_iter().collect())
+    }
+
+    fn wished_attributes() -> Vec<String> {
+        vec![]
+    }
+
+    fn requested_attributes() -> Vec<String> {
+        vec![]
+    }
+}
+
+/// This trait is the inverse of [FromTequilaAttributes]: it converts an object back into the attributes it was constructed from, so that `A::from_tequila_attributes(a.into_tequila_attributes())` gives back `a`.
+/// It should be derived with the [IntoTequilaAttributes](tequila_macros::IntoTequilaAttributes) macro
+pub trait IntoTequilaAttributes {
+    fn into_tequila_attributes(self) -> AttributeMap;
+}
+
+/// Converts a field back into the value of an attribute, as done by the [IntoTequilaAttributes](tequila_macros::IntoTequilaAttributes) macro.
+/// It is implemented for all the types implementing `Display`, and for [Redacted], whose value is not displayable
+pub trait ToTequilaValue {
+    fn to_tequila_value(&self) -> String;
+}
+
+impl<T: std::fmt::Display + ?Sized> ToTequilaValue for T {
+    fn to_tequila_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl IntoTequilaAttributes for () {
+    fn into_tequila_attributes(self) -> AttributeMap {
+        AttributeMap::new()
+    }
+}
+
+impl IntoTequilaAttributes for AttributeMap {
+    fn into_tequila_attributes(self) -> AttributeMap {
+        self
+    }
+}
+
+impl IntoTequilaAttributes for HashMap<String, String> {
+    fn into_tequila_attributes(self) -> AttributeMap {
+        self.into()
+    }
 }
 
 /// Any error that may happen during a call to the API
@@ -67,59 +212,60 @@ fn build_hashmap(str: String) -> Result<HashMap<String, String>, TequilaError> {
 pub enum TequilaError {
     /// The response does not have the expected format
     InvalidResponse,
+    /// A line of the response is not a key value pair
+    MalformedResponse {
+        /// The number of the line, starting at 1
+        line: usize,
+        content: String,
+    },
     /// Network error
     RequestError(reqwest::Error),
+    /// Tequila's servers did not answer in time, see [TequilaClient::with_timeouts]
+    Timeout,
+    /// No service name was given, and the attributes type is not bound to any
+    MissingServiceName,
+    /// A parameter of the request cannot be sent, since it would corrupt the body. Holds the name of the parameter
+    InvalidParameter(String),
     /// The response is missing a required attribute
     MissingAttributes(Vec<String>),
+    /// The response contains attributes that were not expected, see `#[tequila(deny_unknown)]`
+    UnknownAttributes(Vec<String>),
+    /// The value of some attributes could not be parsed into the expected type
+    InvalidAttributes(Vec<String>),
+    /// The request outlived its lifetime, the user must log in again with a new one
+    Expired,
 }
 
-/// Send a request to the API
-/// # Parameters:
-/// - route: the route to call (only the uri of the method, like `"createrequest"`, not the full url)
-/// - body: a list of key value pairs
-async fn send_request<R>(route: String, body: Vec<(&str, String)>) -> Result<R, TequilaError>
-where
-    R: FromTequilaAttributes,
-{
-    let response = reqwest::Client::new()
-        .post(format!("{TEQUILA_URL}/{route}"))
-        .header("Content-Type", "text/plain")
-        .body(body.iter().fold(String::new(), |acc, (key, value)| {
-            format!("{acc}\n{key}={value}")
-        }))
-        .send()
-        .await
-        .map_err(TequilaError::RequestError)?
-        .text()
-        .await
-        .map_err(TequilaError::RequestError)?;
-
-    R::from_tequila_attributes(build_hashmap(response)?)
-}
-
-impl FromTequilaAttributes for CreateRequestResponse {
-    fn from_tequila_attributes(attributes: HashMap<String, String>) -> Result<Self, TequilaError> {
-        Ok(Self {
-            key: attributes
-                .get("key")
-                .ok_or(TequilaError::InvalidResponse)?
-                .clone(),
-        })
-    }
-
-    fn wished_attributes() -> Vec<String> {
-        vec![]
+impl TequilaError {
+    /// Whether the error was caused by the network rather than by the request itself, in which case the call may succeed if it is retried
+    pub fn is_transient(&self) -> bool {
+        match self {
+            TequilaError::RequestError(e) => e.is_timeout() || e.is_connect(),
+            TequilaError::Timeout => true,
+            _ => false,
+        }
     }
 
-    fn requested_attributes() -> Vec<String> {
-        vec![]
+    /// The name of the variant, which can be logged without disclosing the content of the error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TequilaError::InvalidResponse => "invalid_response",
+            TequilaError::MalformedResponse { .. } => "malformed_response",
+            TequilaError::RequestError(_) => "request_error",
+            TequilaError::Timeout => "timeout",
+            TequilaError::MissingServiceName => "missing_service_name",
+            TequilaError::InvalidParameter(_) => "invalid_parameter",
+            TequilaError::MissingAttributes(_) => "missing_attributes",
+            TequilaError::UnknownAttributes(_) => "unknown_attributes",
+            TequilaError::InvalidAttributes(_) => "invalid_attributes",
+            TequilaError::Expired => "expired",
+        }
     }
 }
 
-/// The response from the `"create_request"` route
-struct CreateRequestResponse {
-    key: String,
-}
+/// Error returned when parsing an enum derived with [FromTequilaAttributes](tequila_macros::FromTequilaAttributes) from a value which does not match any of its variants
+#[derive(Debug)]
+pub struct UnknownVariant(pub String);
 
 /// Create a request on the servers. Returns the key of the request
 /// # Parameters
@@ -130,6 +276,10 @@ struct CreateRequestResponse {
 /// - `require`: The filter you want to impose on the user's attributes. It is a parenthesized boolean expression with atomic members of the form : attr1=value1, or attr1. In the former case, attribute attr1 of the user must have the value value1 among its set of values (remember that attributes can be multi valued). In the latter form, attribute attr1 must be present and not null 
 /// - `allow`: In a certain sense, this the contrary of `require`. By default, the Tequila server impose default restrictions on certain attributes values. Using allows can lift some (or all) of these restrictions
 /// - `language`: Language to use in the interaction with the user (login window, errors, ...). The default server's language is set in the server's configuration files. The user will still be able to change the language
+/// - `authstrength`: The minimal strength of the authentification method the user must use. The default is set in the server's configuration files
+///
+/// The call is made with [TequilaClient::shared], use [TequilaClient::create_request] to make it with another client
+#[allow(clippy::too_many_arguments)]
 pub async fn create_request(
     return_url: Url,
     service_name: String,
@@ -138,59 +288,51 @@ pub async fn create_request(
     require: Option<String>,
     allow: Option<String>,
     language: Option<String>,
-) -> Result<String, TequilaError> {
-    Ok(
-        send_request::<CreateRequestResponse>("createrequest".into(), {
-            let mut vec = vec![
-                ("urlaccess", return_url.to_string()),
-                ("service", service_name),
-                ("mode_auth_check", "1".into()),
-            ];
-
-            if !request_attributes.is_empty() {
-                vec.push(("request", request_attributes.join(",")))
-            }
-            if !wish_attributes.is_empty() {
-                vec.push(("wish", wish_attributes.join(",")))
-            }
-            if let Some(require) = require {
-                vec.push(("require", require))
-            }
-            if let Some(allow) = allow {
-                vec.push(("allow", allow))
-            }
-            if let Some(language) = language {
-                vec.push(("language", language))
-            }
-
-            vec
-        })
-        .await?
-        .key,
-    )
+    authstrength: Option<u32>,
+) -> Result<RequestKey, TequilaError> {
+    TequilaClient::shared()
+        .create_request(
+            return_url,
+            service_name,
+            request_attributes,
+            wish_attributes,
+            require,
+            allow,
+            language,
+            authstrength,
+        )
+        .await
 }
 
 /// Fetches the attributes of the user which logged in using the request key. This method can only be called once on the same request. `auth_check` is the token in the url where the user was redirected
-pub async fn fetch_attributes<A>(key: String, auth_check: String) -> Result<A, TequilaError>
+///
+/// The call is made with [TequilaClient::shared], use [TequilaClient::fetch_attributes] to make it with another client
+pub async fn fetch_attributes<A>(
+    key: &RequestKey,
+    auth_check: &AuthCheck,
+) -> Result<A, TequilaError>
 where
     A: FromTequilaAttributes,
 {
-    send_request(
-        "fetchattributes".into(),
-        vec![("key", key), ("auth_check", auth_check)],
-    )
-    .await
+    TequilaClient::shared()
+        .fetch_attributes(key, auth_check)
+        .await
 }
 
 /// Wrapper for the whole procedure. It uses typestate to enforce that the calls are made in the right order:
 /// - WaitingLogin: The request was created, but the `auth_check` was not supplied. At this point, you may use the [key](TequilaRequest::key) and [fetch_attributes](TequilaRequest::fetch_attributes) methods
 /// - LoggedIn: The login was completed. The [attributes](TequilaRequest::attributes) is available to recover the attributes fetched
+/// - Expired: The request outlived its lifetime before the login was completed, see [check_expiry](TequilaRequest::check_expiry)
 pub struct TequilaRequest<A, S>
 where
     A: FromTequilaAttributes,
 {
-    key: String,
+    key: RequestKey,
+    /// In the `WaitingLogin` state, the instance the request was created from, if any. In the `LoggedIn` state, the attributes fetched
     attributes: Option<A>,
+    created_at: Instant,
+    lifetime: Duration,
+    client: TequilaClient,
     _state: PhantomData<S>,
 }
 
@@ -198,30 +340,180 @@ where
 pub struct WaitingLogin;
 /// State of [TequilaRequest]
 pub struct LoggedIn;
+/// State of [TequilaRequest]
+pub struct Expired;
+
+/// The lifetime assumed for a request when [RequestOptions::lifetime] is not set
+pub const DEFAULT_REQUEST_LIFETIME: Duration = Duration::from_secs(10 * 60);
+
+/// Parameters of a request overriding the ones bound to the attributes type. Fields left to `None` keep the type's values
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    pub service_name: Option<String>,
+    pub require: Option<String>,
+    pub allow: Option<String>,
+    pub language: Option<String>,
+    pub authstrength: Option<u32>,
+    /// How long the request is expected to be valid on Tequila's side. Defaults to [DEFAULT_REQUEST_LIFETIME]
+    pub lifetime: Option<Duration>,
+    /// The client making the calls of the request, including `fetch_attributes`. Defaults to [TequilaClient::shared]
+    pub client: Option<TequilaClient>,
+}
+
+impl RequestOptions {
+    /// Completes the parameters left to `None` with the ones bound to the attributes type
+    fn or_bound_to<A>(self) -> Self
+    where
+        A: FromTequilaAttributes,
+    {
+        Self {
+            service_name: self.service_name.or_else(A::service),
+            require: self.require.or_else(A::require),
+            allow: self.allow.or_else(A::allow),
+            language: self.language.or_else(A::language),
+            authstrength: self.authstrength.or_else(A::authstrength),
+            ..self
+        }
+    }
+}
 
 impl TequilaRequest<(), ()> {
-    /// Create a new request handler in the `WaitingLogin` state, with the given `return_url` and `service_name`
+    /// Create a new request handler in the `WaitingLogin` state, with the given `return_url` and `service_name`. The other parameters are the ones bound to the attributes type
     pub async fn new<A>(
         return_url: Url,
         service_name: String,
-    ) -> Result<TequilaRequest<A, WaitingLogin>, TequilaError> // TODO add allow and require
+    ) -> Result<TequilaRequest<A, WaitingLogin>, TequilaError>
+    where
+        A: FromTequilaAttributes,
+    {
+        Self::with_options(
+            return_url,
+            RequestOptions {
+                service_name: Some(service_name),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Create a new request handler in the `WaitingLogin` state, using only the parameters bound to the attributes type. Fails if the type is not bound to a service
+    pub async fn from_type<A>(
+        return_url: Url,
+    ) -> Result<TequilaRequest<A, WaitingLogin>, TequilaError>
     where
         A: FromTequilaAttributes,
     {
-        Ok(TequilaRequest {
-            key: create_request(
+        Self::with_options(return_url, RequestOptions::default()).await
+    }
+
+    /// Create a new request handler in the `WaitingLogin` state, with the parameters bound to the attributes type overriden by `options`
+    pub async fn with_options<A>(
+        return_url: Url,
+        options: RequestOptions,
+    ) -> Result<TequilaRequest<A, WaitingLogin>, TequilaError>
+    where
+        A: FromTequilaAttributes,
+    {
+        Self::start(
+            return_url,
+            options,
+            None,
+            A::requested_attributes(),
+            A::wished_attributes(),
+        )
+        .await
+    }
+
+    /// Create a new request handler in the `WaitingLogin` state, using `instance` as a template. The attributes requested are the ones of the instance, which is also used to construct the attributes fetched.
+    /// This allows types such as [DynamicAttributes] to be configured at runtime
+    pub async fn with_instance<A>(
+        return_url: Url,
+        options: RequestOptions,
+        instance: A,
+    ) -> Result<TequilaRequest<A, WaitingLogin>, TequilaError>
+    where
+        A: FromTequilaAttributes,
+    {
+        let request_attributes = instance.instance_requested_attributes();
+        let wish_attributes = instance.instance_wished_attributes();
+        Self::start(
+            return_url,
+            options,
+            Some(instance),
+            request_attributes,
+            wish_attributes,
+        )
+        .await
+    }
+
+    /// Create the request on the servers and its handler in the `WaitingLogin` state, holding `instance` if it was created from one
+    async fn start<A>(
+        return_url: Url,
+        options: RequestOptions,
+        instance: Option<A>,
+        request_attributes: Vec<String>,
+        wish_attributes: Vec<String>,
+    ) -> Result<TequilaRequest<A, WaitingLogin>, TequilaError>
+    where
+        A: FromTequilaAttributes,
+    {
+        let lifetime = options.lifetime.unwrap_or(DEFAULT_REQUEST_LIFETIME);
+        let client = options
+            .client
+            .clone()
+            .unwrap_or_else(|| TequilaClient::shared().clone());
+        // Tequila starts the lifetime when it receives the call, so the clock must start before sending it
+        let created_at = Instant::now();
+        let key = Self::create::<A>(
+            &client,
+            return_url,
+            options,
+            request_attributes,
+            wish_attributes,
+        )
+        .await?;
+
+        Ok(TequilaRequest::waiting(
+            key, instance, created_at, lifetime, client,
+        ))
+    }
+
+    /// Create the request on the servers, with the parameters bound to the attributes type overriden by `options`
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "TequilaRequest::create", skip_all)
+    )]
+    async fn create<A>(
+        client: &TequilaClient,
+        return_url: Url,
+        options: RequestOptions,
+        request_attributes: Vec<String>,
+        wish_attributes: Vec<String>,
+    ) -> Result<RequestKey, TequilaError>
+    where
+        A: FromTequilaAttributes,
+    {
+        let options = options.or_bound_to::<A>();
+        let key = client
+            .create_request(
                 return_url,
-                service_name,
-                A::requested_attributes(),
-                A::wished_attributes(),
-                None,
-                None,
-                None,
+                options
+                    .service_name
+                    .ok_or(TequilaError::MissingServiceName)
+                    .inspect_err(|e| metrics::notify(|m| m.error(e)))?,
+                request_attributes,
+                wish_attributes,
+                options.require,
+                options.allow,
+                options.language,
+                options.authstrength,
             )
-            .await?,
-            attributes: None,
-            _state: PhantomData,
-        })
+            .await?;
+
+        #[cfg(feature = "tracing")]
+        tracing::info!("request created, waiting for login");
+
+        Ok(key)
     }
 }
 
@@ -229,21 +521,169 @@ impl<A> TequilaRequest<A, WaitingLogin>
 where
     A: FromTequilaAttributes,
 {
+    /// The handler of a request created at `created_at`
+    fn waiting(
+        key: RequestKey,
+        instance: Option<A>,
+        created_at: Instant,
+        lifetime: Duration,
+        client: TequilaClient,
+    ) -> Self {
+        TequilaRequest {
+            key,
+            attributes: instance,
+            created_at,
+            lifetime,
+            client,
+            _state: PhantomData,
+        }
+    }
+
+    /// A request created now with the key `"key"`, for the tests which do not contact Tequila
+    #[cfg(test)]
+    pub(crate) fn for_tests(lifetime: Duration) -> Self {
+        Self::waiting(
+            RequestKey::new("key").unwrap(),
+            None,
+            Instant::now(),
+            lifetime,
+            TequilaClient::new(),
+        )
+    }
+
     /// Returns the request's key. The user must log in at `TEQUILA_URL/auth?requestkey={key}`. Must be in `WaitingLogin` state
-    pub fn key(&self) -> &str {
+    pub fn key(&self) -> &RequestKey {
         &self.key
     }
 
-    /// Fetches the attributes with the auth_check provided. If it succeeds, returns a `TequilaRequest<LoggedIn>`. Must be in `WaitingLogin` state
+    /// The client making the calls of the request, see [RequestOptions::client]
+    pub fn client(&self) -> &TequilaClient {
+        &self.client
+    }
+
+    /// When the request was created
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    /// Whether the request outlived its lifetime, in which case Tequila will not accept it anymore
+    pub fn is_expired(&self) -> bool {
+        self.created_at.elapsed() >= self.lifetime
+    }
+
+    /// The time left before the request expires, zero if it already has
+    pub fn remaining(&self) -> Duration {
+        self.lifetime.saturating_sub(self.created_at.elapsed())
+    }
+
+    /// Moves the request to the `Expired` state if it outlived its lifetime, or returns it unchanged otherwise
+    pub fn check_expiry(self) -> Result<Self, TequilaRequest<A, Expired>> {
+        if self.is_expired() {
+            #[cfg(feature = "tracing")]
+            tracing::info!("request expired");
+            metrics::notify(|m| m.request_expired());
+            Err(TequilaRequest {
+                key: self.key,
+                attributes: self.attributes,
+                created_at: self.created_at,
+                lifetime: self.lifetime,
+                client: self.client,
+                _state: PhantomData,
+            })
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Fetches the attributes with the auth_check provided. If it succeeds, returns a `TequilaRequest<LoggedIn>`. Must be in `WaitingLogin` state.
+    ///
+    /// On failure, the request is handed back along with the error, so that the call can be retried without the user having to log in again.
+    /// Fails with [TequilaError::Expired] without contacting Tequila if the request outlived its lifetime
     pub async fn fetch_attributes(
         self,
-        auth_check: String,
-    ) -> Result<TequilaRequest<A, LoggedIn>, TequilaError> {
-        Ok(TequilaRequest {
-            key: self.key.clone(),
-            attributes: Some(fetch_attributes(self.key, auth_check).await?),
-            _state: PhantomData::<LoggedIn>,
-        })
+        auth_check: &AuthCheck,
+    ) -> Result<TequilaRequest<A, LoggedIn>, (TequilaError, TequilaRequest<A, WaitingLogin>)> {
+        let client = self.client.clone();
+        self.fetch_attributes_with(auth_check, &client).await
+    }
+
+    /// Same as [fetch_attributes](Self::fetch_attributes), but makes the call with `client` instead of the request's, for instance to give it another timeout:
+    /// ```ignore
+    /// let client = request.client().with_timeout(Duration::from_secs(5));
+    /// request.fetch_attributes_with(&auth_check, &client).await
+    /// ```
+    /// A client derived with [with_timeout](TequilaClient::with_timeout) keeps the connect timeout of the original one. To change the connect timeout of a single call, pass a client created with [with_timeouts](TequilaClient::with_timeouts), which does not share the connections of the request's client
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "TequilaRequest::fetch_attributes", skip_all)
+    )]
+    pub async fn fetch_attributes_with(
+        self,
+        auth_check: &AuthCheck,
+        client: &TequilaClient,
+    ) -> Result<TequilaRequest<A, LoggedIn>, (TequilaError, TequilaRequest<A, WaitingLogin>)> {
+        if self.is_expired() {
+            #[cfg(feature = "tracing")]
+            tracing::info!("request expired, attributes not fetched");
+            metrics::notify(|m| {
+                m.request_expired();
+                m.error(&TequilaError::Expired);
+            });
+            return Err((TequilaError::Expired, self));
+        }
+
+        let attributes = match &self.attributes {
+            Some(instance) => client
+                .fetch(&self.key, auth_check)
+                .await
+                .and_then(|a| instance.instance_from_tequila_attributes(a)),
+            None => client
+                .fetch(&self.key, auth_check)
+                .await
+                .and_then(A::from_tequila_attributes),
+        };
+        client::notify_login(&attributes);
+
+        #[cfg(feature = "tracing")]
+        match &attributes {
+            Ok(_) => tracing::info!("user logged in"),
+            Err(e) => tracing::info!(error = e.kind(), "could not fetch attributes, request kept"),
+        }
+
+        match attributes {
+            Ok(attributes) => Ok(TequilaRequest {
+                key: self.key,
+                attributes: Some(attributes),
+                created_at: self.created_at,
+                lifetime: self.lifetime,
+                client: self.client,
+                _state: PhantomData::<LoggedIn>,
+            }),
+            Err(e) => Err((e, self)),
+        }
+    }
+
+    /// Same as [fetch_attributes](Self::fetch_attributes), but retries the call according to `policy` as long as it fails with a transient error.
+    /// Note that Tequila only answers once to a given request: if the response was lost after being sent, the retries will fail
+    pub async fn fetch_attributes_with_retry(
+        self,
+        auth_check: &AuthCheck,
+        policy: &RetryPolicy,
+    ) -> Result<TequilaRequest<A, LoggedIn>, (TequilaError, TequilaRequest<A, WaitingLogin>)> {
+        let mut request = self;
+        let mut retry = 0;
+        loop {
+            match request.fetch_attributes(auth_check).await {
+                Err((e, r)) if e.is_transient() && retry < policy.max_retries => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(error = e.kind(), retry, "retrying");
+                    tokio::time::sleep(policy.backoff(retry)).await;
+                    retry += 1;
+                    request = r;
+                }
+                result => return result,
+            }
+        }
     }
 }
 
@@ -256,3 +696,87 @@ where
         self.attributes.as_ref().unwrap()
     }
 }
+
+impl<A> TequilaRequest<A, Expired>
+where
+    A: FromTequilaAttributes,
+{
+    /// Returns the key of the expired request, which Tequila will not accept anymore
+    pub fn key(&self) -> &RequestKey {
+        &self.key
+    }
+
+    /// Recovers the instance the request was created from, if any, so that a new request can be created with [TequilaRequest::with_instance]
+    pub fn into_instance(self) -> Option<A> {
+        self.attributes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(FromTequilaAttributes)]
+    #[tequila(
+        no_check,
+        service = "Bound",
+        language = "fr",
+        authstrength = 2,
+        require = "a"
+    )]
+    struct Bound;
+
+    #[test]
+    fn options_default_to_the_bound_parameters() {
+        let options = RequestOptions::default().or_bound_to::<Bound>();
+
+        assert_eq!(options.service_name.as_deref(), Some("Bound"));
+        assert_eq!(options.language.as_deref(), Some("fr"));
+        assert_eq!(options.authstrength, Some(2));
+        assert_eq!(options.require.as_deref(), Some("a"));
+        assert_eq!(options.allow, None);
+    }
+
+    #[test]
+    fn options_override_the_bound_parameters() {
+        let options = RequestOptions {
+            service_name: Some("Override".into()),
+            language: Some("en".into()),
+            authstrength: Some(1),
+            allow: Some("b".into()),
+            ..Default::default()
+        }
+        .or_bound_to::<Bound>();
+
+        assert_eq!(options.service_name.as_deref(), Some("Override"));
+        assert_eq!(options.language.as_deref(), Some("en"));
+        assert_eq!(options.authstrength, Some(1));
+        assert_eq!(options.require.as_deref(), Some("a"));
+        assert_eq!(options.allow.as_deref(), Some("b"));
+
+        assert_eq!(
+            RequestOptions::default().or_bound_to::<()>().service_name,
+            None
+        );
+    }
+
+    #[test]
+    fn zero_lifetime_is_expired() {
+        let request = TequilaRequest::<(), _>::for_tests(Duration::ZERO);
+
+        assert!(request.is_expired());
+        assert_eq!(request.remaining(), Duration::ZERO);
+        let expired = request.check_expiry().err().unwrap();
+        assert_eq!(expired.key(), &RequestKey::new("key").unwrap());
+    }
+
+    #[test]
+    fn default_lifetime_is_not_expired() {
+        let request = TequilaRequest::<(), _>::for_tests(DEFAULT_REQUEST_LIFETIME);
+
+        assert!(!request.is_expired());
+        assert!(request.remaining() > Duration::ZERO);
+        assert!(request.remaining() <= DEFAULT_REQUEST_LIFETIME);
+        assert!(request.check_expiry().is_ok());
+    }
+}
@@ -0,0 +1,37 @@
+use std::{sync::OnceLock, time::Duration};
+
+use crate::TequilaError;
+
+/// Observer notified of the outcome of the calls to Tequila, to feed counters and histograms. All the methods do nothing by default.
+///
+/// It is installed for the whole process with [set_metrics]
+pub trait Metrics: Send + Sync {
+    /// A request was created on Tequila's servers
+    fn request_created(&self) {}
+
+    /// The attributes of a user were fetched successfully
+    fn login_completed(&self) {}
+
+    /// A request outlived its lifetime before the login was completed
+    fn request_expired(&self) {}
+
+    /// A call failed. See [TequilaError::kind] to count the errors by kind without recording their content
+    fn error(&self, _error: &TequilaError) {}
+
+    /// A call to `route` (such as `"createrequest"`) got a response, or failed at the transport level, after `latency`
+    fn latency(&self, _route: &str, _latency: Duration) {}
+}
+
+static METRICS: OnceLock<Box<dyn Metrics>> = OnceLock::new();
+
+/// Installs the observer notified by the crate. It can only be set once, a second call returns the observer given
+pub fn set_metrics(metrics: impl Metrics + 'static) -> Result<(), Box<dyn Metrics>> {
+    METRICS.set(Box::new(metrics))
+}
+
+/// Notifies the observer, if one was installed
+pub(crate) fn notify(f: impl FnOnce(&dyn Metrics)) {
+    if let Some(metrics) = METRICS.get() {
+        f(metrics.as_ref())
+    }
+}
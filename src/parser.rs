@@ -0,0 +1,119 @@
+//! Parser for the responses of Tequila's API. They are composed of `key=value` lines, with the following rules:
+//! - lines may end with `\n` or `\r\n`, and trailing whitespace is ignored
+//! - blank lines and lines starting with `#` are ignored
+//! - the key ends at the first `=`, so values may contain `=`
+//! - a line starting with a space or a tab continues the value of the previous line, the two being separated by a line feed
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::{AttributeMap, TequilaError};
+
+/// Decodes the body of a response. The charset declared in the `Content-Type` header is honored if there is one, otherwise the body is decoded as UTF-8, falling back to latin-1 if it is not valid
+pub fn decode(body: &[u8], content_type: Option<&str>) -> String {
+    let declared = content_type
+        .and_then(|ct| {
+            ct.split(';').find_map(|p| {
+                let (name, value) = p.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("charset")
+                    .then(|| value.trim().trim_matches('"'))
+            })
+        })
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()));
+
+    match declared {
+        Some(encoding) if encoding != UTF_8 => {
+            encoding.decode_without_bom_handling(body).0.into_owned()
+        }
+        _ => match std::str::from_utf8(body) {
+            Ok(s) => s.into(),
+            Err(_) => body.iter().map(|&b| char::from(b)).collect(),
+        },
+    }
+}
+
+/// Parses a response into an attribute map. Fails with [TequilaError::MalformedResponse] on the first line which does not follow the format
+pub fn parse(response: &str) -> Result<AttributeMap, TequilaError> {
+    let mut entries: Vec<(String, String)> = vec![];
+
+    for (i, raw) in response.lines().enumerate() {
+        let line = raw.trim_end();
+        let malformed = || TequilaError::MalformedResponse {
+            line: i + 1,
+            content: raw.into(),
+        };
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with([' ', '\t']) {
+            let (_, value) = entries.last_mut().ok_or_else(malformed)?;
+            value.push('\n');
+            value.push_str(line.trim_start());
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                entries.push((key.trim().into(), value.into()))
+            }
+            _ => return Err(malformed()),
+        }
+    }
+
+    Ok(entries.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(map: &AttributeMap) -> Vec<(&str, &str)> {
+        map.iter().collect()
+    }
+
+    #[test]
+    fn parses_crlf_comments_and_blank_lines() {
+        let map = parse("# comment\r\nkey=abc\r\n\r\nname=a=b  \r\nname=c\r\n").unwrap();
+        assert_eq!(
+            pairs(&map),
+            [("key", "abc"), ("name", "a=b"), ("name", "c")]
+        );
+    }
+
+    #[test]
+    fn joins_continuation_lines() {
+        let map = parse("address=line 1\n line 2\n\tline 3\nname=x\n").unwrap();
+        assert_eq!(
+            pairs(&map),
+            [("address", "line 1\nline 2\nline 3"), ("name", "x")]
+        );
+    }
+
+    #[test]
+    fn reports_the_malformed_line() {
+        match parse("key=abc\n# comment\nnot a pair\n") {
+            Err(TequilaError::MalformedResponse { line, content }) => {
+                assert_eq!(line, 3);
+                assert_eq!(content, "not a pair");
+            }
+            r => panic!("unexpected result {r:?}"),
+        }
+        assert!(matches!(
+            parse(" orphan continuation\n"),
+            Err(TequilaError::MalformedResponse { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("key=abc\r\n=value\r\n"),
+            Err(TequilaError::MalformedResponse { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn decodes_with_the_declared_charset() {
+        assert_eq!(decode("é".as_bytes(), None), "é");
+        assert_eq!(decode(&[0xe9], None), "é");
+        assert_eq!(decode(&[0xe9], Some("text/plain; charset=ISO-8859-1")), "é");
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use url::Url;
+
+use crate::{
+    metrics, FromTequilaAttributes, RequestOptions, TequilaError, TequilaRequest, WaitingLogin,
+    DEFAULT_REQUEST_LIFETIME,
+};
+
+/// How long the pool waits before trying again to fill itself, when it could not be filled up to its capacity
+const REFILL_RETRY: Duration = Duration::from_secs(30);
+
+/// A pool of requests created in advance, so that a user can be redirected to the login page without waiting for the `createrequest` call.
+///
+/// All the requests of a pool are created with the same attributes type and options. The pool is refilled in the background after each [get](KeyPool::get).
+/// A background task also discards the requests as soon as they would expire within `min_remaining`, and replaces them, so that an idle pool stays ready. It ends when the pool and all its clones are dropped.
+///
+/// Cloning the pool is cheap, the clones sharing the same requests. It must be created and used from within a Tokio runtime with the time driver enabled
+pub struct KeyPool<A>
+where
+    A: FromTequilaAttributes,
+{
+    inner: Arc<Inner<A>>,
+}
+
+struct Inner<A>
+where
+    A: FromTequilaAttributes,
+{
+    return_url: Url,
+    options: RequestOptions,
+    capacity: usize,
+    min_remaining: Duration,
+    state: Mutex<State<A>>,
+    /// Whether a background task is already refilling the pool
+    refilling: AtomicBool,
+}
+
+struct State<A>
+where
+    A: FromTequilaAttributes,
+{
+    requests: VecDeque<TequilaRequest<A, WaitingLogin>>,
+    /// The number of requests being created, counted against the capacity so that concurrent fills do not overshoot it
+    pending: usize,
+}
+
+/// A slot of the pool reserved for a request being created, given back when dropped if the request was not pushed
+struct Reservation<'a, A>
+where
+    A: FromTequilaAttributes,
+{
+    state: &'a Mutex<State<A>>,
+    done: bool,
+}
+
+impl<A> Reservation<'_, A>
+where
+    A: FromTequilaAttributes,
+{
+    fn push(mut self, request: TequilaRequest<A, WaitingLogin>) {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push_back(request);
+        state.pending -= 1;
+        self.done = true;
+    }
+}
+
+impl<A> Drop for Reservation<'_, A>
+where
+    A: FromTequilaAttributes,
+{
+    fn drop(&mut self) {
+        if !self.done {
+            self.state.lock().unwrap().pending -= 1;
+        }
+    }
+}
+
+/// Clears the `refilling` flag when the refill task ends, even if it panicked
+struct RefillGuard<'a>(&'a AtomicBool);
+
+impl Drop for RefillGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl<A> Clone for KeyPool<A>
+where
+    A: FromTequilaAttributes,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A> KeyPool<A>
+where
+    A: FromTequilaAttributes + Send + Sync + 'static,
+{
+    /// Creates an empty pool, keeping up to `capacity` requests created with `return_url` and `options` (see [TequilaRequest::with_options]).
+    /// The requests are handed out only if they will still be valid for `min_remaining`, so that the user has the time to log in.
+    ///
+    /// The pool is filled by [fill](Self::fill), by [get](Self::get), or by the background task within 30 seconds
+    /// # Panics
+    /// If `min_remaining` is not shorter than the lifetime of the requests (see [RequestOptions::lifetime]), since no request would ever be fresh enough
+    pub fn new(
+        return_url: Url,
+        options: RequestOptions,
+        capacity: usize,
+        min_remaining: Duration,
+    ) -> Self {
+        let inner = Arc::new(Inner::new(return_url, options, capacity, min_remaining));
+        tokio::spawn(Self::maintain(Arc::downgrade(&inner)));
+        Self { inner }
+    }
+
+    /// Fills the pool up to its capacity, waiting for all the requests to be created. Useful to warm the pool up at startup.
+    /// The requests being created by a concurrent fill count towards the capacity, so they are not waited for
+    pub async fn fill(&self) -> Result<(), TequilaError> {
+        self.inner.fill().await
+    }
+
+    /// Takes a request from the pool, or creates one if the pool is empty, and refills the pool in the background
+    pub async fn get(&self) -> Result<TequilaRequest<A, WaitingLogin>, TequilaError> {
+        let request = self.inner.take();
+        self.refill();
+
+        match request {
+            Some(request) => Ok(request),
+            None => {
+                TequilaRequest::with_options(
+                    self.inner.return_url.clone(),
+                    self.inner.options.clone(),
+                )
+                .await
+            }
+        }
+    }
+
+    /// The number of requests available, some of which may have become stale since the last check
+    pub fn len(&self) -> usize {
+        self.inner.state.lock().unwrap().requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Spawns a task refilling the pool, unless one is already running. If a request cannot be created, the pool stays partially filled until the next call or the next check of the background task
+    fn refill(&self) {
+        if self.inner.refilling.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            loop {
+                let guard = RefillGuard(&inner.refilling);
+                let result = inner.fill().await;
+                #[cfg(feature = "tracing")]
+                if let Err(e) = &result {
+                    tracing::warn!(error = e.kind(), "could not refill the key pool");
+                }
+                drop(guard);
+
+                // A `get` made after the fill ended but before the flag was cleared did not spawn a refill, so the pool is checked again
+                if result.is_err()
+                    || inner.is_full()
+                    || inner.refilling.swap(true, Ordering::AcqRel)
+                {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Discards the requests as they become stale and refills the pool, until the pool is dropped
+    async fn maintain(inner: Weak<Inner<A>>) {
+        loop {
+            let Some(next_check) = inner.upgrade().map(|inner| inner.next_check()) else {
+                return;
+            };
+            tokio::time::sleep_until(next_check.into()).await;
+
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            inner.discard_stale();
+            KeyPool { inner }.refill();
+        }
+    }
+}
+
+impl<A> Inner<A>
+where
+    A: FromTequilaAttributes,
+{
+    fn new(
+        return_url: Url,
+        options: RequestOptions,
+        capacity: usize,
+        min_remaining: Duration,
+    ) -> Self {
+        let lifetime = options.lifetime.unwrap_or(DEFAULT_REQUEST_LIFETIME);
+        assert!(
+            min_remaining < lifetime,
+            "The minimal remaining time of the pooled requests ({min_remaining:?}) must be shorter than their lifetime ({lifetime:?})"
+        );
+
+        Self {
+            return_url,
+            options,
+            capacity,
+            min_remaining,
+            state: Mutex::new(State {
+                requests: VecDeque::with_capacity(capacity),
+                pending: 0,
+            }),
+            refilling: AtomicBool::new(false),
+        }
+    }
+
+    /// Pops the oldest request which is still fresh enough, discarding the others
+    fn take(&self) -> Option<TequilaRequest<A, WaitingLogin>> {
+        let mut state = self.state.lock().unwrap();
+        let mut discarded = 0;
+        let request = loop {
+            match state.requests.pop_front() {
+                Some(request) if request.remaining() >= self.min_remaining => break Some(request),
+                Some(_) => discarded += 1,
+                None => break None,
+            }
+        };
+        drop(state);
+
+        notify_discarded(discarded);
+        request
+    }
+
+    /// Removes the requests which are not fresh enough anymore
+    fn discard_stale(&self) {
+        let discarded = self.state.lock().unwrap().discard_stale(self.min_remaining);
+        notify_discarded(discarded);
+    }
+
+    /// Whether the pool holds, or is creating, as many requests as its capacity
+    fn is_full(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.requests.len() + state.pending >= self.capacity
+    }
+
+    /// When the oldest request becomes stale, or when to try again to fill the pool if it is not full
+    fn next_check(&self) -> Instant {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        let stale = state
+            .requests
+            .iter()
+            .map(|r| now + r.remaining().saturating_sub(self.min_remaining))
+            .min();
+        let retry = now + REFILL_RETRY;
+
+        match stale {
+            Some(stale) if state.requests.len() + state.pending >= self.capacity => stale,
+            Some(stale) => stale.min(retry),
+            None => retry,
+        }
+    }
+
+    /// Creates requests until the pool is full. Fails with [TequilaError::Expired] if a request is already stale once created, since creating more would not help
+    async fn fill(&self) -> Result<(), TequilaError> {
+        while let Some(reservation) = self.reserve() {
+            let request =
+                TequilaRequest::with_options(self.return_url.clone(), self.options.clone()).await?;
+            if request.remaining() < self.min_remaining {
+                notify_discarded(1);
+                return Err(TequilaError::Expired);
+            }
+            reservation.push(request);
+        }
+        Ok(())
+    }
+
+    /// Reserves a slot for a new request, discarding the stale ones, unless the pool is full
+    fn reserve(&self) -> Option<Reservation<'_, A>> {
+        let mut state = self.state.lock().unwrap();
+        let discarded = state.discard_stale(self.min_remaining);
+        let full = state.requests.len() + state.pending >= self.capacity;
+        if !full {
+            state.pending += 1;
+        }
+        drop(state);
+
+        notify_discarded(discarded);
+        (!full).then(|| Reservation {
+            state: &self.state,
+            done: false,
+        })
+    }
+}
+
+impl<A> State<A>
+where
+    A: FromTequilaAttributes,
+{
+    /// Removes the requests which will expire within `min_remaining`, returning how many were removed
+    fn discard_stale(&mut self, min_remaining: Duration) -> usize {
+        let before = self.requests.len();
+        self.requests.retain(|r| r.remaining() >= min_remaining);
+        before - self.requests.len()
+    }
+}
+
+/// Reports the requests discarded by the pool as expired, since no user will log in with them
+fn notify_discarded(discarded: usize) {
+    if discarded > 0 {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(discarded, "discarded stale requests from the key pool");
+        metrics::notify(|m| (0..discarded).for_each(|_| m.request_expired()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(capacity: usize) -> Inner<()> {
+        Inner::new(
+            Url::parse("https://example.com").unwrap(),
+            RequestOptions::default(),
+            capacity,
+            Duration::from_secs(60),
+        )
+    }
+
+    fn counts(pool: &Inner<()>) -> (usize, usize) {
+        let state = pool.state.lock().unwrap();
+        (state.requests.len(), state.pending)
+    }
+
+    #[test]
+    fn reservations_count_against_the_capacity() {
+        let pool = pool(2);
+
+        let first = pool.reserve().unwrap();
+        let _second = pool.reserve().unwrap();
+        assert!(pool.reserve().is_none());
+        assert!(pool.is_full());
+        assert_eq!(counts(&pool), (0, 2));
+
+        drop(first);
+        assert_eq!(counts(&pool), (0, 1));
+        assert!(pool.reserve().is_some());
+    }
+
+    #[test]
+    fn pushed_reservations_become_requests() {
+        let pool = pool(1);
+
+        pool.reserve()
+            .unwrap()
+            .push(TequilaRequest::for_tests(Duration::from_secs(600)));
+        assert_eq!(counts(&pool), (1, 0));
+        assert!(pool.reserve().is_none());
+
+        assert!(pool.take().is_some());
+        assert_eq!(counts(&pool), (0, 0));
+        assert!(pool.take().is_none());
+    }
+
+    #[test]
+    fn stale_requests_are_discarded() {
+        let pool = pool(2);
+        let (first, second) = (pool.reserve().unwrap(), pool.reserve().unwrap());
+        first.push(TequilaRequest::for_tests(Duration::ZERO));
+        second.push(TequilaRequest::for_tests(Duration::from_secs(30)));
+        assert_eq!(counts(&pool), (2, 0));
+
+        pool.discard_stale();
+        assert_eq!(counts(&pool), (0, 0));
+
+        pool.reserve()
+            .unwrap()
+            .push(TequilaRequest::for_tests(Duration::ZERO));
+        assert!(pool.take().is_none());
+        assert_eq!(counts(&pool), (0, 0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn min_remaining_must_be_shorter_than_the_lifetime() {
+        Inner::<()>::new(
+            Url::parse("https://example.com").unwrap(),
+            RequestOptions {
+                lifetime: Some(Duration::from_secs(60)),
+                ..Default::default()
+            },
+            1,
+            Duration::from_secs(60),
+        );
+    }
+
+    #[test]
+    fn next_check_waits_for_the_oldest_request() {
+        let pool = pool(1);
+        assert!(pool.next_check() >= Instant::now() + REFILL_RETRY - Duration::from_secs(1));
+
+        pool.reserve()
+            .unwrap()
+            .push(TequilaRequest::for_tests(Duration::from_secs(600)));
+        let next_check = pool.next_check() - Instant::now();
+        assert!(next_check <= Duration::from_secs(540));
+        assert!(next_check > Duration::from_secs(530));
+    }
+}
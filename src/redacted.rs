@@ -0,0 +1,51 @@
+use std::{fmt, str::FromStr};
+
+use crate::ToTequilaValue;
+
+/// Wrapper for an attribute value holding personal data, such as an email or a SCIPER, which displays `<redacted>` in `Debug`.
+///
+/// It implements `FromStr` like the value it wraps, so it can be used as a field type, including with the [IntoTequilaAttributes](tequila_macros::IntoTequilaAttributes) macro through [ToTequilaValue].
+/// It does not implement `Display`, so that the value cannot be logged by accident; use [expose](Self::expose) to read it
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Redacted<T>(T);
+
+impl<T> Redacted<T> {
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// The value wrapped
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Redacted<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: FromStr> FromStr for Redacted<T> {
+    type Err = T::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl<T: ToTequilaValue> ToTequilaValue for Redacted<T> {
+    fn to_tequila_value(&self) -> String {
+        self.0.to_tequila_value()
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+/// Policy used to retry a call which failed because of a transient error (see [TequilaError::is_transient](crate::TequilaError::is_transient)).
+/// The delay between two attempts starts at `initial_backoff`, and is multiplied by `multiplier` after each attempt, up to `max_backoff`
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The number of retries after the first attempt
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+            multiplier: 2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay to wait before the given retry, starting at 0
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.multiplier
+            .checked_pow(retry)
+            .and_then(|factor| self.initial_backoff.checked_mul(factor))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_up_to_the_maximum() {
+        let policy = RetryPolicy::default();
+        let backoffs = (0..6).map(|r| policy.backoff(r)).collect::<Vec<_>>();
+
+        assert_eq!(
+            backoffs,
+            [200, 400, 800, 1600, 2000, 2000].map(Duration::from_millis)
+        );
+    }
+
+    #[test]
+    fn backoff_overflow_gives_the_maximum() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(40), policy.max_backoff);
+        assert_eq!(policy.backoff(u32::MAX), policy.max_backoff);
+
+        let policy = RetryPolicy {
+            initial_backoff: Duration::MAX,
+            max_backoff: Duration::from_secs(1),
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), policy.max_backoff);
+    }
+}
@@ -0,0 +1,118 @@
+//! Mapping of the groups and units of a user to the roles of an application
+//! ```
+//! use tequila::{
+//!     attributes::{Groups, Unit},
+//!     roles::RoleMapper,
+//! };
+//!
+//! #[derive(Clone, PartialEq)]
+//! enum Role {
+//!     Admin,
+//!     Staff,
+//! }
+//!
+//! let mapper = RoleMapper::new()
+//!     .group("myapp-admins", Role::Admin)
+//!     .unit("IC", Role::Staff);
+//!
+//! let groups: Groups = "myapp-admins,other".parse().unwrap();
+//! assert!(mapper.has_role(&groups, &[], &Role::Admin));
+//! assert!(!mapper.has_role(&groups, &[], &Role::Staff));
+//!
+//! let units: Vec<Unit> = vec!["IC-IT/IC/EPFL".parse().unwrap()];
+//! assert!(mapper.has_role(&Groups::default(), &units, &Role::Staff));
+//! ```
+
+use crate::attributes::{Groups, Person, Unit};
+
+/// What a user must satisfy to be granted a role
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    Group(String),
+    AllGroups(Vec<String>),
+    Unit(String),
+}
+
+impl Condition {
+    fn matches(&self, groups: &Groups, units: &[Unit]) -> bool {
+        match self {
+            Condition::Group(group) => groups.contains(group),
+            Condition::AllGroups(all) => groups.all_of(all.iter().map(String::as_str)),
+            Condition::Unit(unit) => units.iter().any(|u| u.is_within(unit)),
+        }
+    }
+}
+
+/// A list of rules granting roles to the users matching them. A user gets the roles of all the rules they match
+#[derive(Debug, Clone)]
+pub struct RoleMapper<R> {
+    rules: Vec<(Condition, R)>,
+}
+
+impl<R> Default for RoleMapper<R> {
+    fn default() -> Self {
+        Self { rules: vec![] }
+    }
+}
+
+impl<R> RoleMapper<R>
+where
+    R: Clone + PartialEq,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants `role` to the members of `group`
+    pub fn group(mut self, group: impl Into<String>, role: R) -> Self {
+        self.rules.push((Condition::Group(group.into()), role));
+        self
+    }
+
+    /// Grants `role` to the users which are members of all the `groups`
+    pub fn all_groups<S: Into<String>>(
+        mut self,
+        groups: impl IntoIterator<Item = S>,
+        role: R,
+    ) -> Self {
+        self.rules.push((
+            Condition::AllGroups(groups.into_iter().map(Into::into).collect()),
+            role,
+        ));
+        self
+    }
+
+    /// Grants `role` to the users belonging to `unit` or to any unit under it, see [Unit::is_within]
+    pub fn unit(mut self, unit: impl Into<String>, role: R) -> Self {
+        self.rules.push((Condition::Unit(unit.into()), role));
+        self
+    }
+
+    /// The roles granted to a user, without duplicates, in the order of the rules
+    pub fn roles(&self, groups: &Groups, units: &[Unit]) -> Vec<R> {
+        let mut roles: Vec<R> = vec![];
+        for (condition, role) in &self.rules {
+            if condition.matches(groups, units) && !roles.contains(role) {
+                roles.push(role.clone());
+            }
+        }
+        roles
+    }
+
+    /// Whether a user is granted `role`. Suitable as a guard before serving a request
+    pub fn has_role(&self, groups: &Groups, units: &[Unit], role: &R) -> bool {
+        self.rules
+            .iter()
+            .any(|(condition, r)| r == role && condition.matches(groups, units))
+    }
+
+    /// The roles granted to a person, see [roles](Self::roles)
+    pub fn roles_of(&self, person: &Person) -> Vec<R> {
+        self.roles(&person.group, &person.units)
+    }
+
+    /// Whether a person is granted `role`, see [has_role](Self::has_role)
+    pub fn person_has_role(&self, person: &Person, role: &R) -> bool {
+        self.has_role(&person.group, &person.units, role)
+    }
+}
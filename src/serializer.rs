@@ -0,0 +1,61 @@
+//! Serializer for the bodies of the requests sent to Tequila's API. They use the same format as the responses: one `key=value` pair per line.
+//! Since there is no way to escape a line break, the values containing one are rejected instead of being sent, as they could inject extra parameters. Values may contain `=`, as only the first one of a line separates the key from the value
+
+use crate::TequilaError;
+
+/// Serializes key value pairs into a body. Fails with [TequilaError::InvalidParameter] if a key is empty or contains `=`, or if a key or a value contains a line break
+pub fn serialize(body: &[(&str, String)]) -> Result<String, TequilaError> {
+    body.iter()
+        .try_fold(String::new(), |mut acc, (key, value)| {
+            let line_break = |s: &str| s.contains(['\n', '\r']);
+            if key.is_empty() || key.contains('=') || line_break(key) || line_break(value) {
+                return Err(TequilaError::InvalidParameter(key.to_string()));
+            }
+
+            acc.push_str(key);
+            acc.push('=');
+            acc.push_str(value);
+            acc.push('\n');
+            Ok(acc)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(body: &[(&str, String)]) -> Option<String> {
+        match serialize(body) {
+            Err(TequilaError::InvalidParameter(key)) => Some(key),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn writes_one_line_per_pair() {
+        assert_eq!(
+            serialize(&[("service", "My app".into()), ("require", "a=b&c".into())]).unwrap(),
+            "service=My app\nrequire=a=b&c\n"
+        );
+    }
+
+    #[test]
+    fn rejects_line_breaks_in_values() {
+        assert_eq!(
+            invalid(&[("service", "x\nallow=all".into())]).as_deref(),
+            Some("service")
+        );
+        assert_eq!(
+            invalid(&[("service", "x\r".into())]).as_deref(),
+            Some("service")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_keys() {
+        assert_eq!(invalid(&[("a\nb", "x".into())]).as_deref(), Some("a\nb"));
+        assert_eq!(invalid(&[("a\rb", "x".into())]).as_deref(), Some("a\rb"));
+        assert_eq!(invalid(&[("a=b", "x".into())]).as_deref(), Some("a=b"));
+        assert_eq!(invalid(&[("", "x".into())]).as_deref(), Some(""));
+    }
+}
@@ -0,0 +1,134 @@
+use std::{fmt, str::FromStr};
+
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+/// The maximal length accepted for a token
+const MAX_LENGTH: usize = 256;
+
+/// Error returned when creating a token from a string which does not have the expected format
+#[derive(Debug)]
+pub struct InvalidToken;
+
+/// Checks that a token is non empty, not too long and only made of ASCII alphanumeric characters
+fn validate(token: &str) -> Result<(), InvalidToken> {
+    if !token.is_empty()
+        && token.len() <= MAX_LENGTH
+        && token.bytes().all(|b| b.is_ascii_alphanumeric())
+    {
+        Ok(())
+    } else {
+        Err(InvalidToken)
+    }
+}
+
+macro_rules! token {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        ///
+        /// Its value is redacted from `Debug`, compared in constant time and erased from memory when dropped. Use [expose](Self::expose) to read it
+        #[derive(Clone)]
+        pub struct $name(String);
+
+        impl $name {
+            /// Creates the token, checking its format
+            pub fn new(token: impl Into<String>) -> Result<Self, InvalidToken> {
+                let mut token = token.into();
+                match validate(&token) {
+                    Ok(()) => Ok(Self(token)),
+                    Err(e) => {
+                        token.zeroize();
+                        Err(e)
+                    }
+                }
+            }
+
+            /// The value of the token
+            pub fn expose(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = InvalidToken;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                validate(s)?;
+                Ok(Self(s.into()))
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(concat!(stringify!($name), "(<redacted>)"))
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.as_bytes().ct_eq(other.0.as_bytes()).into()
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                self.0.zeroize()
+            }
+        }
+    };
+}
+
+token!(
+    /// The key identifying a request, returned by Tequila when it is created
+    RequestKey
+);
+
+token!(
+    /// The token appended to the return url after a successful login, needed to fetch the attributes
+    AuthCheck
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_alphanumeric_tokens() {
+        assert_eq!(RequestKey::new("abc123").unwrap().expose(), "abc123");
+        assert_eq!("ABC".parse::<AuthCheck>().unwrap().expose(), "ABC");
+        assert!(RequestKey::new("a".repeat(MAX_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        for token in ["", "a b", "a&b=c", "abc\n", "é", "a-b"] {
+            assert!(RequestKey::new(token).is_err(), "{token:?}");
+            assert!(token.parse::<AuthCheck>().is_err(), "{token:?}");
+        }
+        assert!(RequestKey::new("a".repeat(MAX_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn debug_is_redacted() {
+        assert_eq!(
+            format!("{:?}", RequestKey::new("secret").unwrap()),
+            "RequestKey(<redacted>)"
+        );
+        assert_eq!(
+            format!("{:?}", AuthCheck::new("secret").unwrap()),
+            "AuthCheck(<redacted>)"
+        );
+    }
+
+    #[test]
+    fn compares_values() {
+        let key = RequestKey::new("abc").unwrap();
+
+        assert_eq!(key, RequestKey::new("abc").unwrap());
+        assert_eq!(key, key.clone());
+        assert_ne!(key, RequestKey::new("abd").unwrap());
+        assert_ne!(key, RequestKey::new("abcd").unwrap());
+    }
+}
@@ -0,0 +1,260 @@
+use proc_macro::TokenStream;
+use proc_macro_error::{abort, emit_error};
+use syn::{__private::quote::quote, parse_quote, Fields, Generics, ItemEnum, LitStr, Type};
+
+use crate::{check_attribute, set_dummy_impl, uses_type_params};
+
+pub struct Variant {
+    pub ident: syn::Ident,
+    /// The value of the attribute which selects this variant
+    pub value: String,
+    /// The type wrapped by the variant, if any
+    pub inner: Option<Type>,
+}
+
+/// Parses the attributes on the enum and its variants. Returns the tag (if any), whether the configuration should be checked, and the metadata of the variants
+pub fn parse_enum(enum_: &ItemEnum) -> (Option<LitStr>, bool, Vec<Variant>) {
+    // Get the attributes on the enum:
+    // - tag is the key of the attribute selecting the variant
+    // - no_check disables the verification with the server's configuration
+    let mut tag: Option<LitStr> = None;
+    let mut check_config = true;
+    if let Some(attr) = enum_.attrs.iter().find(|a| a.path().is_ident("tequila")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                tag = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("no_check") {
+                check_config = false;
+                Ok(())
+            } else {
+                Err(meta.error("unrecognized attribute"))
+            }
+        })
+        .unwrap_or_else(|e| emit_error!(attr, e.to_string()))
+    }
+
+    // Generate the required metadata for all variants
+    let variants = enum_
+        .variants
+        .iter()
+        .filter_map(|v| {
+            let mut value = v.ident.to_string();
+            if let Some(attr) = v.attrs.iter().find(|a| a.path().is_ident("tequila")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        value = meta.value()?.parse::<LitStr>()?.value();
+                        Ok(())
+                    } else {
+                        Err(meta.error("unrecognized attribute"))
+                    }
+                })
+                .unwrap_or_else(|e| emit_error!(attr, e.to_string()))
+            }
+
+            let inner = match &v.fields {
+                Fields::Unit => None,
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                    Some(fields.unnamed[0].ty.clone())
+                }
+                fields => {
+                    emit_error!(
+                        fields,
+                        "Only unit variants and variants wrapping a single type are supported"
+                    );
+                    return None;
+                }
+            };
+
+            Some(Variant {
+                ident: v.ident.clone(),
+                value,
+                inner,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    (tag, check_config, variants)
+}
+
+/// Derives `FromStr` for enums with only unit variants, and `FromTequilaAttributes` for enums with a `tag` attribute
+pub fn derive_enum(enum_: ItemEnum) -> TokenStream {
+    let (tag, check_config, variants) = parse_enum(&enum_);
+    let id = enum_.ident;
+
+    let Some(tag) = tag else {
+        if let Some(v) = variants.iter().find(|v| v.inner.is_some()) {
+            abort!(
+                v.ident,
+                "Enums with data-carrying variants require a `#[tequila(tag = \"...\")]` attribute"
+            )
+        }
+        return derive_from_str(&id, &enum_.generics, &variants).into();
+    };
+
+    set_dummy_impl(&id, &enum_.generics);
+
+    // If required, check the tag with the server's configuration
+    if check_config {
+        check_attribute(&tag.value(), tag.span());
+    }
+
+    let arms = variants.iter().map(|Variant { ident, value, inner }| match inner {
+        Some(ty) => quote! {
+            #value => <#ty as ::tequila::FromTequilaAttributes>::from_flattened_attributes(attributes, &known).map(Self::#ident),
+        },
+        None => quote! {
+            #value => Ok(Self::#ident),
+        },
+    });
+    let inner_types = variants
+        .iter()
+        .filter_map(|v| v.inner.as_ref())
+        .collect::<Vec<_>>();
+
+    let known = if inner_types.is_empty() {
+        quote!(let _ = parent_known;)
+    } else {
+        quote! {
+            let known = [
+                <Self as ::tequila::FromTequilaAttributes>::requested_attributes(),
+                <Self as ::tequila::FromTequilaAttributes>::wished_attributes(),
+                parent_known.to_vec(),
+            ]
+            .concat();
+        }
+    };
+
+    // Unit-only enums may also be used as field types
+    let from_str = if inner_types.is_empty() {
+        derive_from_str(&id, &enum_.generics, &variants)
+    } else {
+        quote!()
+    };
+
+    // The wrapped types depending on type parameters must be convertible
+    let mut generics = enum_.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in inner_types
+        .iter()
+        .filter(|ty| uses_type_params(ty, &enum_.generics))
+    {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: ::tequila::FromTequilaAttributes));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Since the variant is not known when creating the request, only the tag and the attributes requested by all variants are requested, the others being wished.
+    // The filters of a variant only apply when the tag selects it
+    let variant_count = variants.len();
+    let values = variants.iter().map(|v| &v.value).collect::<Vec<_>>();
+    let for_each_variant = |method: proc_macro2::TokenStream, none: proc_macro2::TokenStream| {
+        variants
+            .iter()
+            .map(|v| match &v.inner {
+                Some(ty) => quote!(<#ty as ::tequila::FromTequilaAttributes>::#method()),
+                None => none.clone(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let requested_lists = for_each_variant(quote!(requested_attributes), quote!(vec![]));
+    let wished_lists = for_each_variant(quote!(wished_attributes), quote!(vec![]));
+    let requires = for_each_variant(quote!(require), quote!(None));
+    let allows = for_each_variant(quote!(allow), quote!(None));
+
+    quote! {
+        impl #impl_generics ::tequila::FromTequilaAttributes for #id #ty_generics #where_clause {
+            fn from_tequila_attributes(attributes: ::tequila::AttributeMap) -> Result<Self, ::tequila::TequilaError> {
+                <Self as ::tequila::FromTequilaAttributes>::from_flattened_attributes(attributes, &[])
+            }
+
+            // The attributes of the other variants are known, so that the wrapped types do not consider them unknown
+            fn from_flattened_attributes(
+                attributes: ::tequila::AttributeMap,
+                parent_known: &[::std::string::String],
+            ) -> Result<Self, ::tequila::TequilaError> {
+                #known
+                let Some(value) = attributes.get(#tag).map(String::from) else {
+                    return Err(::tequila::TequilaError::MissingAttributes(vec![#tag.into()]));
+                };
+
+                match value.as_str() {
+                    #(#arms)*
+                    _ => Err(::tequila::TequilaError::InvalidAttributes(vec![#tag.into()])),
+                }
+            }
+
+            fn wished_attributes() -> Vec<String> {
+                let requested = <Self as ::tequila::FromTequilaAttributes>::requested_attributes();
+                let mut attributes: Vec<String> = vec![];
+                for a in [#(#wished_lists,)* #(#requested_lists,)*].concat() {
+                    if !requested.contains(&a) && !attributes.contains(&a) {
+                        attributes.push(a);
+                    }
+                }
+                attributes
+            }
+
+            fn requested_attributes() -> Vec<String> {
+                let variants: [Vec<String>; #variant_count] = [#(#requested_lists,)*];
+                let mut attributes: Vec<String> = vec![#tag.into()];
+                for a in variants.first().cloned().unwrap_or_default() {
+                    if variants.iter().all(|v| v.contains(&a)) && !attributes.contains(&a) {
+                        attributes.push(a);
+                    }
+                }
+                attributes
+            }
+
+            fn require() -> Option<String> {
+                let filters: [(&str, Option<String>); #variant_count] = [#((#values, #requires),)*];
+                if filters.iter().all(|(_, f)| f.is_none()) {
+                    return None;
+                }
+                Some(
+                    filters
+                        .into_iter()
+                        .map(|(value, filter)| match filter {
+                            Some(filter) => format!("({}={}&({}))", #tag, value, filter),
+                            None => format!("{}={}", #tag, value),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("|"),
+                )
+            }
+
+            fn allow() -> Option<String> {
+                let filters = [#(#allows,)*].into_iter().flatten().collect::<Vec<_>>();
+                (!filters.is_empty()).then(|| format!("({})", filters.join(")|(")))
+            }
+        }
+
+        #from_str
+    }
+    .into()
+}
+
+/// Implements `FromStr` for an enum with only unit variants
+fn derive_from_str(
+    id: &syn::Ident,
+    generics: &Generics,
+    variants: &[Variant],
+) -> proc_macro2::TokenStream {
+    let values = variants.iter().map(|v| &v.value);
+    let idents = variants.iter().map(|v| &v.ident);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::std::str::FromStr for #id #ty_generics #where_clause {
+            type Err = ::tequila::UnknownVariant;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    #(#values => Ok(Self::#idents),)*
+                    _ => Err(::tequila::UnknownVariant(s.into())),
+                }
+            }
+        }
+    }
+}
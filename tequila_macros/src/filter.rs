@@ -0,0 +1,167 @@
+/// An error in a filter expression, with the position (in characters) where it occured
+#[derive(Debug)]
+pub struct FilterError {
+    pub position: usize,
+    pub message: String,
+}
+
+/// Parser for the filters used by the `require` and `allow` parameters. They follow this grammar:
+/// ```text
+/// expr   := term ('|' term)*
+/// term   := factor ('&' factor)*
+/// factor := '!' factor | '(' expr ')' | name ('=' value)?
+/// ```
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    /// The attributes referenced so far
+    attributes: Vec<String>,
+}
+
+const OPERATORS: [char; 5] = ['|', '&', '!', '(', ')'];
+
+impl Parser {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, FilterError> {
+        Err(FilterError {
+            position: self.position,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_whitespace())
+        {
+            self.position += 1;
+        }
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<(), FilterError> {
+        self.term()?;
+        while self.eat('|') {
+            self.term()?;
+        }
+        Ok(())
+    }
+
+    fn term(&mut self) -> Result<(), FilterError> {
+        self.factor()?;
+        while self.eat('&') {
+            self.factor()?;
+        }
+        Ok(())
+    }
+
+    fn factor(&mut self) -> Result<(), FilterError> {
+        if self.eat('!') {
+            self.factor()
+        } else if self.eat('(') {
+            self.expr()?;
+            if !self.eat(')') {
+                return self.error("Expected `)`");
+            }
+            Ok(())
+        } else {
+            self.atom()
+        }
+    }
+
+    fn atom(&mut self) -> Result<(), FilterError> {
+        self.peek();
+        let start = self.position;
+        while self
+            .chars
+            .get(self.position)
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            self.position += 1;
+        }
+        if start == self.position {
+            return match self.chars.get(self.position) {
+                Some(c) => self.error(format!("Expected an attribute name, found `{c}`")),
+                None => self.error("Expected an attribute name"),
+            };
+        }
+        let name = self.chars[start..self.position].iter().collect::<String>();
+        if !self.attributes.contains(&name) {
+            self.attributes.push(name);
+        }
+
+        // The value extends up to the next operator
+        if self.eat('=') {
+            let start = self.position;
+            while self
+                .chars
+                .get(self.position)
+                .is_some_and(|c| !OPERATORS.contains(c))
+            {
+                self.position += 1;
+            }
+            if self.chars[start..self.position]
+                .iter()
+                .all(|c| c.is_whitespace())
+            {
+                return self.error("Expected a value after `=`");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a filter, returning the names of the attributes it references
+pub fn parse(filter: &str) -> Result<Vec<String>, FilterError> {
+    let mut parser = Parser {
+        chars: filter.chars().collect(),
+        position: 0,
+        attributes: vec![],
+    };
+
+    parser.expr()?;
+    if let Some(c) = parser.peek() {
+        return parser.error(format!("Unexpected `{c}`"));
+    }
+
+    Ok(parser.attributes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(filter: &str) -> (usize, String) {
+        let e = parse(filter).unwrap_err();
+        (e.position, e.message)
+    }
+
+    #[test]
+    fn returns_the_referenced_attributes() {
+        assert_eq!(parse("a=b&(c|!d)").unwrap(), ["a", "c", "d"]);
+        assert_eq!(parse(" a = x y | a ").unwrap(), ["a"]);
+        assert_eq!(parse("group=my-group.1").unwrap(), ["group"]);
+    }
+
+    #[test]
+    fn reports_the_error_position() {
+        assert_eq!(error("a="), (2, "Expected a value after `=`".into()));
+        assert_eq!(error("(a"), (2, "Expected `)`".into()));
+        assert_eq!(
+            error("a&|b"),
+            (2, "Expected an attribute name, found `|`".into())
+        );
+        assert_eq!(error("a)"), (1, "Unexpected `)`".into()));
+        assert_eq!(error(""), (0, "Expected an attribute name".into()));
+    }
+}
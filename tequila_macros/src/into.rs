@@ -0,0 +1,226 @@
+use proc_macro::TokenStream;
+use proc_macro_error::{abort, set_dummy};
+use syn::{__private::quote::quote, parse_quote, Fields, Generics, Ident, ItemEnum, ItemStruct};
+
+use crate::{
+    enums::parse_enum, generic_argument, parse_fields, uses_type_params, Field, FieldType,
+};
+
+/// Create a dummy implementation in case the macro fails, to avoid the error: "<type> does not implement IntoTequilaAttributes"
+fn set_dummy_into_impl(id: &Ident, generics: &Generics) {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    set_dummy(quote! {
+        impl #impl_generics ::tequila::IntoTequilaAttributes for #id #ty_generics #where_clause {
+            fn into_tequila_attributes(self) -> ::tequila::AttributeMap {
+                unimplemented!();
+            }
+        }
+    });
+}
+
+/// Derives `IntoTequilaAttributes` for a struct, and `Display` for a newtype
+pub fn derive_into_struct(struct_: ItemStruct) -> TokenStream {
+    let id = struct_.ident;
+    set_dummy_into_impl(&id, &struct_.generics);
+
+    // The keys are already checked by `FromTequilaAttributes`
+    let fields = parse_fields(&struct_.fields, false);
+
+    // Flattened and rest fields are inserted last, so that the struct's own fields take precedence
+    let extensions = fields.iter().filter_map(|Field { member, _type, .. }| match _type {
+        FieldType::Flatten => Some(quote! {
+            attributes.extend(::tequila::IntoTequilaAttributes::into_tequila_attributes(self.#member));
+        }),
+        FieldType::Rest => Some(quote! {
+            attributes.extend(self.#member);
+        }),
+        _ => None,
+    });
+    let insertions = fields.iter().filter_map(
+        |Field {
+             member,
+             attribute: key,
+             _type,
+             ..
+         }| match _type {
+            FieldType::Other => Some(quote! {
+                attributes.insert(#key, ::tequila::ToTequilaValue::to_tequila_value(&self.#member));
+            }),
+            FieldType::Option => Some(quote! {
+                if let Some(v) = self.#member {
+                    attributes.insert(#key, ::tequila::ToTequilaValue::to_tequila_value(&v));
+                }
+            }),
+            // The values are joined with commas into a single entry, as Tequila sends them
+            FieldType::Vec => Some(quote! {
+                if !self.#member.is_empty() {
+                    let values = self.#member
+                        .iter()
+                        .map(::tequila::ToTequilaValue::to_tequila_value)
+                        .collect::<::std::vec::Vec<_>>();
+                    attributes.insert(#key, values.join(","));
+                }
+            }),
+            FieldType::Collect => Some(quote! {
+                let values = (&self.#member)
+                    .into_iter()
+                    .map(|v| ::tequila::ToTequilaValue::to_tequila_value(&v))
+                    .collect::<::std::vec::Vec<_>>();
+                if !values.is_empty() {
+                    attributes.insert(#key, values.join(","));
+                }
+            }),
+            _ => None,
+        },
+    );
+
+    // The values of the fields depending on type parameters must be convertible into values, and the flattened fields into attributes
+    let mut generics = struct_.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for f in &fields {
+        let ty = match f._type {
+            FieldType::Option | FieldType::Vec => generic_argument(&f.ty).unwrap_or(&f.ty),
+            _ => &f.ty,
+        };
+        if !uses_type_params(ty, &struct_.generics) {
+            continue;
+        }
+        match f._type {
+            FieldType::Other | FieldType::Option | FieldType::Vec => where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::tequila::ToTequilaValue)),
+            FieldType::Collect => {
+                where_clause
+                    .predicates
+                    .push(parse_quote!(for<'a> &'a #ty: ::std::iter::IntoIterator));
+                where_clause.predicates.push(parse_quote!(
+                    for<'a> <&'a #ty as ::std::iter::IntoIterator>::Item: ::tequila::ToTequilaValue
+                ));
+            }
+            FieldType::Flatten => where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::tequila::IntoTequilaAttributes)),
+            FieldType::Rest => where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::std::iter::IntoIterator<Item = (String, String)>)),
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Newtypes are displayed as their single value, mirroring their `FromStr` implementation
+    let display = match (&struct_.fields, fields.as_slice()) {
+        (
+            Fields::Unnamed(_),
+            [Field {
+                _type: FieldType::Other,
+                ..
+            }],
+        ) => quote! {
+            impl #impl_generics ::std::fmt::Display for #id #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    f.write_str(&::tequila::ToTequilaValue::to_tequila_value(&self.0))
+                }
+            }
+        },
+        _ => quote!(),
+    };
+
+    quote! {
+        impl #impl_generics ::tequila::IntoTequilaAttributes for #id #ty_generics #where_clause {
+            fn into_tequila_attributes(self) -> ::tequila::AttributeMap {
+                let mut attributes = ::tequila::AttributeMap::new();
+                #(#insertions)*
+                #(#extensions)*
+                attributes
+            }
+        }
+
+        #display
+    }
+    .into()
+}
+
+/// Derives `Display` for enums with only unit variants, and `IntoTequilaAttributes` for enums with a `tag` attribute
+pub fn derive_into_enum(enum_: ItemEnum) -> TokenStream {
+    let (tag, _, variants) = parse_enum(&enum_);
+    let id = enum_.ident;
+    let (impl_generics, ty_generics, where_clause) = enum_.generics.split_for_impl();
+
+    // Unit-only enums are displayed as the value of their variant, mirroring their `FromStr` implementation
+    let display = if variants.iter().all(|v| v.inner.is_none()) {
+        let idents = variants.iter().map(|v| &v.ident);
+        let values = variants.iter().map(|v| &v.value);
+        quote! {
+            impl #impl_generics ::std::fmt::Display for #id #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    f.write_str(match self {
+                        #(Self::#idents => #values,)*
+                    })
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let Some(tag) = tag else {
+        if let Some(v) = variants.iter().find(|v| v.inner.is_some()) {
+            abort!(
+                v.ident,
+                "Enums with data-carrying variants require a `#[tequila(tag = \"...\")]` attribute"
+            )
+        }
+        return display.into();
+    };
+
+    set_dummy_into_impl(&id, &enum_.generics);
+
+    let arms = variants.iter().map(|v| {
+        let ident = &v.ident;
+        let value = &v.value;
+        match v.inner {
+            Some(_) => quote! {
+                Self::#ident(inner) => {
+                    let mut attributes = ::tequila::AttributeMap::new();
+                    attributes.insert(#tag, #value);
+                    attributes.extend(::tequila::IntoTequilaAttributes::into_tequila_attributes(inner));
+                    attributes
+                }
+            },
+            None => quote! {
+                Self::#ident => {
+                    let mut attributes = ::tequila::AttributeMap::new();
+                    attributes.insert(#tag, #value);
+                    attributes
+                }
+            },
+        }
+    });
+
+    // The wrapped types depending on type parameters must be convertible
+    let mut generics = enum_.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in variants
+        .iter()
+        .filter_map(|v| v.inner.as_ref())
+        .filter(|ty| uses_type_params(ty, &enum_.generics))
+    {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: ::tequila::IntoTequilaAttributes));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::tequila::IntoTequilaAttributes for #id #ty_generics #where_clause {
+            fn into_tequila_attributes(self) -> ::tequila::AttributeMap {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+
+        #display
+    }
+    .into()
+}
@@ -4,27 +4,39 @@ use std::sync::OnceLock;
 
 use config::TequilaConfig;
 use proc_macro::TokenStream;
-use proc_macro2::Ident;
+use proc_macro2::{Ident, TokenTree};
 use proc_macro_error::{
     abort_call_site, emit_call_site_warning, emit_error, proc_macro_error, set_dummy,
 };
 use syn::{
-    spanned::Spanned,
-    Item, LitStr, Meta, MetaList, Path, Type, TypePath,
     __private::{quote::quote, Span},
+    parse::{ParseStream, Parser},
+    parse_quote,
+    spanned::Spanned,
+    GenericArgument, Generics, Index, Item, ItemStruct, LitInt, LitStr, Member, Meta, MetaList,
+    Path, PathArguments, Token, Type, TypePath,
 };
 
 mod config;
+mod enums;
+mod filter;
+mod into;
 
 const TEQUILA_URL: &str = "https://tequila.epfl.ch/cgi-bin/tequila/";
 
 static CONFIG: OnceLock<Option<TequilaConfig>> = OnceLock::new();
 
-#[derive(Debug)]
 struct Field {
     name: Option<String>,
+    /// How the field is accessed, by name or by position
+    member: Member,
     attribute: String,
+    /// Span of the key, or of the field if the key is implicit
+    span: Span,
+    ty: Type,
     _type: FieldType,
+    /// Whether the value is hidden from the generated `Debug` implementation
+    sensitive: bool,
 }
 
 #[derive(Debug)]
@@ -32,6 +44,22 @@ enum FieldType {
     Other,
     Option,
     Vec,
+    /// The field is a collection built from all the values of the key, like a `Vec`
+    Collect,
+    /// The field implements `FromTequilaAttributes` itself and is built from the same attributes
+    Flatten,
+    /// The field collects all the attributes which are not used by other fields
+    Rest,
+}
+
+impl FieldType {
+    /// Whether the field takes its value from a key of its own
+    fn has_key(&self) -> bool {
+        matches!(
+            self,
+            FieldType::Other | FieldType::Option | FieldType::Vec | FieldType::Collect
+        )
+    }
 }
 
 const OPTION_PATHES: [(&str, bool); 3] = [
@@ -57,6 +85,98 @@ fn compare_pathes(pathes: &[(&str, bool)], path: &Path) -> bool {
     })
 }
 
+/// Returns the first type argument of a path type, like `T` in `Option<T>`
+fn generic_argument(ty: &Type) -> Option<&Type> {
+    let Type::Path(TypePath { path, .. }) = ty else {
+        return None;
+    };
+    match &path.segments.last()?.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|a| match a {
+            GenericArgument::Type(t) => Some(t),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Checks whether a type mentions any of the type parameters of the item
+fn uses_type_params(ty: &Type, generics: &Generics) -> bool {
+    fn visit(ts: proc_macro2::TokenStream, params: &[&Ident]) -> bool {
+        ts.into_iter().any(|tt| match tt {
+            TokenTree::Ident(id) => params.contains(&&id),
+            TokenTree::Group(g) => visit(g.stream(), params),
+            _ => false,
+        })
+    }
+
+    let params = generics.type_params().map(|p| &p.ident).collect::<Vec<_>>();
+    !params.is_empty() && visit(quote!(#ty), &params)
+}
+
+/// Computes the edit distance between two strings, counting insertions, deletions, substitutions and transpositions of adjacent characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+/// Whether an attribute at `distance` from `key` is close enough to be suggested as a fix for a typo
+fn is_typo(key: &str, distance: usize) -> bool {
+    distance <= key.chars().count().max(3) / 3
+}
+
+/// Emits an error if the server's configuration is available and does not support the attribute, suggesting the closest supported ones
+fn check_attribute(key: &str, span: Span) {
+    let Some(config) = get_config() else {
+        return;
+    };
+    if config.attributes.iter().any(|a| a == key) {
+        return;
+    }
+
+    let mut closest = config
+        .attributes
+        .iter()
+        .map(|a| (edit_distance(key, a), a))
+        .collect::<Vec<_>>();
+    closest.sort();
+    let closest = closest.into_iter().take(3).collect::<Vec<_>>();
+
+    match closest.first() {
+        // Only suggest an attribute if it is close enough to be a typo
+        Some((distance, best)) if is_typo(key, *distance) => emit_error!(
+            span,
+            "Invalid attribute \"{}\"", key;
+            help = "Did you mean \"{}\"?", best
+        ),
+        _ => emit_error!(
+            span,
+            "Invalid attribute \"{}\"", key;
+            note = "Closest attributes are [{}]", closest.iter().map(|(_, a)| a.as_str()).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
 fn get_config() -> &'static Option<TequilaConfig> {
     if let Some(cfg) = CONFIG.get() {
         cfg
@@ -73,19 +193,66 @@ fn get_config() -> &'static Option<TequilaConfig> {
 
 /// Derives the `FromTequilaAttributes` trait. The fields of type `Option` or `Vec` are considered optional.
 ///
-/// You may set the key of the value that the field should take using the `#[tequila("key")]` attribute. If no such attribute is present, the key will default to the field's name
+/// You may set the key of the value that the field should take using the `#[tequila("key")]` attribute. If no such attribute is present, the key will default to the field's name.
+/// Positional fields of tuple structs have no name, so the attribute is mandatory. A newtype such as `struct Sciper(#[tequila("uniqueid")] u32);` also implements `FromStr`, and can be used as a field type
+///
+/// A field whose type implements `FromTequilaAttributes` can be marked with `#[tequila(flatten)]`. Its requested and wished attributes are merged into the parent's, and it is constructed from the same attributes
+///
+/// A single `HashMap<String, String>` (or `AttributeMap`) field can be marked with `#[tequila(rest)]` to collect all the attributes which are not used by the other fields.
+/// Setting `#[tequila(deny_unknown)]` on the structure instead makes the conversion fail when such attributes are received.
+/// When the structure is flattened into another, the attributes used by the other type are not considered unknown
+///
+/// The values are parsed using `FromStr`, so any such type can be used for the fields (or inside the `Option` and `Vec`).
+/// If a key is received several times, `Vec` fields collect all its values, while other fields take the first one.
+/// Other collections of strings, such as `Groups`, can be built the same way by marking the field with `#[tequila(collect)]`: it is then optional, and empty when the key is not received.
+/// The values of `Vec` and `collect` fields are split on commas, and the whitespace around each piece is removed.
+/// Generic types are supported: a `FromStr` (or `FromTequilaAttributes` for flattened fields) bound is added for each field depending on a type parameter.
+///
+/// The filters sent when creating the request can be set with `#[tequila(require = "...", allow = "...")]` on a structure. Their syntax and the attributes they reference are checked at compile time.
+/// The filters of flattened fields are combined with the structure's own
+///
+/// The other parameters of the request can be bound to the structure with `#[tequila(service = "...", language = "...", authstrength = N)]`
+///
+/// On an enum, the macro behaves differently depending on its variants:
+/// - if all variants are units, it implements `FromStr`, matching the value with the name of the variants. It can then be used as a field type
+/// - otherwise, the enum must have a `#[tequila(tag = "key")]` attribute. The value of this attribute selects the variant, which may either be a unit or wrap a single type implementing `FromTequilaAttributes`.
+///   Only the tag and the attributes requested by every variant are requested, the others are wished. The filters of the wrapped types only apply when the tag selects their variant
+///
+/// In both cases, the value matching a variant can be changed using `#[tequila(rename = "value")]`
+///
+/// Fields holding personal data can be marked with `#[tequila(sensitive)]`. The structure then implements `Debug` (and must not derive it), displaying `<redacted>` in place of their values.
+/// The flag may follow the key, as in `#[tequila("email", sensitive)]`
 #[proc_macro_error]
 #[proc_macro_derive(FromTequilaAttributes, attributes(tequila))]
 pub fn derive_from_tequila_attributes(ts: TokenStream) -> TokenStream {
-    let Ok(Item::Struct(struct_))= syn::parse::<Item>(ts) else {
-        abort_call_site!("FromTequilaAttributes can only be used on structs")
-    };
+    match syn::parse::<Item>(ts) {
+        Ok(Item::Struct(struct_)) => derive_struct(struct_),
+        Ok(Item::Enum(enum_)) => enums::derive_enum(enum_),
+        _ => abort_call_site!("FromTequilaAttributes can only be used on structs and enums"),
+    }
+}
 
-    // Create a dummy implementation in case the macro fails, to avoid the error: "<type> does not implement FromTequilaAttributes"
-    let id = struct_.ident;
+/// Derives the `IntoTequilaAttributes` trait, the inverse of `FromTequilaAttributes`. It accepts the same attributes, so that both can be derived on the same type.
+///
+/// The values are converted using `ToTequilaValue`, that is `Display` or the value wrapped by `Redacted`: the values of a `Vec` or `collect` field are joined with commas into a single entry, as Tequila sends them, and the `None` values and empty collections are skipped.
+/// Converting back gives the same value, except for the `Vec` values which are empty, contain a comma or are surrounded by whitespace: since Tequila separates multiple values with commas, they are dropped, split or trimmed when parsed.
+/// Newtypes and enums with only unit variants implement `Display` instead, mirroring their `FromStr` implementation
+#[proc_macro_error]
+#[proc_macro_derive(IntoTequilaAttributes, attributes(tequila))]
+pub fn derive_into_tequila_attributes(ts: TokenStream) -> TokenStream {
+    match syn::parse::<Item>(ts) {
+        Ok(Item::Struct(struct_)) => into::derive_into_struct(struct_),
+        Ok(Item::Enum(enum_)) => into::derive_into_enum(enum_),
+        _ => abort_call_site!("IntoTequilaAttributes can only be used on structs and enums"),
+    }
+}
+
+/// Create a dummy implementation in case the macro fails, to avoid the error: "<type> does not implement FromTequilaAttributes"
+fn set_dummy_impl(id: &Ident, generics: &Generics) {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     set_dummy(quote! {
-        impl ::tequila::FromTequilaAttributes for #id {
-            fn from_tequila_attributes(attributes: ::std::collections::HashMap<String, String>) -> Result<Self, ::tequila::TequilaError> {
+        impl #impl_generics ::tequila::FromTequilaAttributes for #id #ty_generics #where_clause {
+            fn from_tequila_attributes(attributes: ::tequila::AttributeMap) -> Result<Self, ::tequila::TequilaError> {
                 unimplemented!();
             }
 
@@ -98,66 +265,119 @@ pub fn derive_from_tequila_attributes(ts: TokenStream) -> TokenStream {
             }
         }
     });
+}
 
-    // Get the no_check attibutes on the structure, which disables the verification with the server's configuration
-    let mut check_config = true;
-    if let Some(attr) = struct_.attrs.iter().find(|a| a.path().is_ident("tequila")) {
-        attr.parse_nested_meta(|meta| {
-            if meta.path.is_ident("no_check") {
-                check_config = false;
-                Ok(())
-            } else {
-                Err(meta.error("unrecognized attribute"))
-            }
-        })
-        .unwrap_or_else(|e| emit_error!(attr, e.to_string()))
-    }
-
-    // Generate the required metadata for all fields
-    let fields = struct_
-        .fields
+/// Generates the required metadata for all fields, checking their keys with the server's configuration if `check_config` is set
+fn parse_fields(fields: &syn::Fields, check_config: bool) -> Vec<Field> {
+    fields
         .iter()
-        .filter_map(|f| {
+        .enumerate()
+        .filter_map(|(i, f)| {
+            let member = match &f.ident {
+                Some(id) => Member::Named(id.clone()),
+                None => Member::Unnamed(Index::from(i)),
+            };
+
             // Get the key which this field will take its value from
             let mut key = f.ident.as_ref().map(|id| id.to_string());
             let mut key_span = f.span();
-            if let Some(attr) = f.attrs.iter().find(|a| a.path().is_ident("tequila")) {
-                if let Ok(val) = attr.parse_args::<LitStr>() {
-                    key_span = val.span();
-                    key = Some(val.value());
-                } else {
-                    match &attr.meta {
-                        Meta::List(MetaList { tokens, .. }) => {
-                            emit_error!(tokens.span(), "Expected string litteral")
-                        }
-                        o => {
-                            emit_error!(
-                                o,
-                                "Should be a list containing exactly one string litteral"
-                            )
+            let mut flatten = false;
+            let mut rest = false;
+            let mut sensitive = false;
+            let mut collect = false;
+            for attr in f.attrs.iter().filter(|a| a.path().is_ident("tequila")) {
+                match &attr.meta {
+                    // The key may be followed by flags, as in `#[tequila("email", sensitive)]`
+                    Meta::List(MetaList { tokens, .. }) => {
+                        if let Err(e) = attr.parse_args_with(|input: ParseStream| {
+                            if input.peek(LitStr) {
+                                let val = input.parse::<LitStr>()?;
+                                key_span = val.span();
+                                key = Some(val.value());
+                                if input.is_empty() {
+                                    return Ok(());
+                                }
+                                input.parse::<Token![,]>()?;
+                            }
+                            syn::meta::parser(|meta| {
+                                if meta.path.is_ident("flatten") {
+                                    flatten = true;
+                                    Ok(())
+                                } else if meta.path.is_ident("rest") {
+                                    rest = true;
+                                    Ok(())
+                                } else if meta.path.is_ident("sensitive") {
+                                    sensitive = true;
+                                    Ok(())
+                                } else if meta.path.is_ident("collect") {
+                                    collect = true;
+                                    Ok(())
+                                } else {
+                                    Err(meta.error("Expected string litteral, `flatten`, `rest`, `sensitive` or `collect`"))
+                                }
+                            })
+                            .parse2(input.parse()?)
+                        }) {
+                            emit_error!(tokens.span(), e.to_string());
+                            return None;
                         }
                     }
-                    return None;
+                    o => {
+                        emit_error!(
+                            o,
+                            "Should be a list containing exactly one string litteral"
+                        );
+                        return None;
+                    }
+                }
+            }
+
+            // Flattened and rest fields have no key of their own
+            if flatten || rest {
+                if flatten && rest {
+                    emit_error!(f, "A field cannot be both `flatten` and `rest`");
                 }
+                if collect {
+                    emit_error!(f, "A `flatten` or `rest` field cannot be `collect`");
+                }
+                return Some(Field {
+                    name: f.ident.as_ref().map(|i| i.to_string()),
+                    member,
+                    attribute: String::new(),
+                    span: f.span(),
+                    ty: f.ty.clone(),
+                    _type: if flatten {
+                        FieldType::Flatten
+                    } else {
+                        FieldType::Rest
+                    },
+                    sensitive,
+                });
             }
 
+            // Positional fields have no name to default to
+            let Some(key) = key else {
+                emit_error!(
+                    f,
+                    "Positional fields require a key";
+                    help = "Add `#[tequila(\"key\")]` to the field"
+                );
+                return None;
+            };
+
             // If required, check the key with the server's configuration
             if check_config {
-                if let (Some(key), Some(config)) = (&key, get_config()) {
-                    if !config.attributes.contains(key) {
-                        emit_error!(
-                            key_span,
-                            "Invalid attribute \"{}\"", key;
-                            note = "Available attributes are [{}]", config.attributes.join(", ")
-                        )
-                    }
-                }
+                check_attribute(&key, key_span);
             }
 
             Some(Field {
                 name: f.ident.as_ref().map(|i| i.to_string()),
-                attribute: key.unwrap_or_default(),
+                member,
+                attribute: key,
+                span: key_span,
+                ty: f.ty.clone(),
                 _type: match &f.ty {
+                    _ if collect => FieldType::Collect,
                     Type::Path(TypePath { path, .. }) => {
                         if compare_pathes(&OPTION_PATHES, path) {
                             FieldType::Option
@@ -172,14 +392,85 @@ pub fn derive_from_tequila_attributes(ts: TokenStream) -> TokenStream {
                         FieldType::Other
                     }
                 },
+                sensitive,
             })
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+fn derive_struct(struct_: ItemStruct) -> TokenStream {
+    let id = struct_.ident;
+    set_dummy_impl(&id, &struct_.generics);
+
+    // Get the attibutes on the structure:
+    // - no_check disables the verification with the server's configuration
+    // - deny_unknown rejects the attributes which are not used by any field
+    // - require and allow are the filters sent when creating a request
+    // - service, language and authstrength are the other parameters of the request
+    let mut check_config = true;
+    let mut deny_unknown = false;
+    let mut require: Option<LitStr> = None;
+    let mut allow: Option<LitStr> = None;
+    let mut service: Option<LitStr> = None;
+    let mut language: Option<LitStr> = None;
+    let mut authstrength: Option<LitInt> = None;
+    if let Some(attr) = struct_.attrs.iter().find(|a| a.path().is_ident("tequila")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("no_check") {
+                check_config = false;
+                Ok(())
+            } else if meta.path.is_ident("deny_unknown") {
+                deny_unknown = true;
+                Ok(())
+            } else if meta.path.is_ident("require") {
+                require = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("allow") {
+                allow = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("service") {
+                service = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("language") {
+                language = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("authstrength") {
+                let value: LitInt = meta.value()?.parse()?;
+                value.base10_parse::<u32>()?;
+                authstrength = Some(value);
+                Ok(())
+            } else {
+                Err(meta.error("unrecognized attribute"))
+            }
+        })
+        .unwrap_or_else(|e| emit_error!(attr, e.to_string()))
+    }
+
+    // Check the syntax of the filters, and the attributes they reference
+    for filter in [&require, &allow].into_iter().flatten() {
+        match filter::parse(&filter.value()) {
+            Ok(attributes) if check_config => attributes
+                .iter()
+                .for_each(|a| check_attribute(a, filter.span())),
+            Ok(_) => {}
+            Err(e) => emit_error!(
+                filter,
+                "Invalid filter: {}", e.message;
+                note = "At position {} in \"{}\"", e.position, filter.value()
+            ),
+        }
+    }
+
+    let fields = parse_fields(&struct_.fields, check_config);
 
     let wished_attributes = fields
         .iter()
         .filter_map(|f| {
-            matches!(f._type, FieldType::Option | FieldType::Vec).then(|| {
+            matches!(
+                f._type,
+                FieldType::Option | FieldType::Vec | FieldType::Collect
+            )
+            .then(|| {
                 let f = f.attribute.clone();
                 quote! {
                     #f.into(),
@@ -206,25 +497,152 @@ pub fn derive_from_tequila_attributes(ts: TokenStream) -> TokenStream {
             acc
         });
 
+    // Two fields cannot take their value from the same key
+    for (i, f) in fields.iter().enumerate() {
+        if !f._type.has_key() {
+            continue;
+        }
+        if let Some(first) = fields[..i]
+            .iter()
+            .find(|g| g._type.has_key() && g.attribute == f.attribute)
+        {
+            emit_error!(
+                f.span,
+                "Attribute \"{}\" is mapped by several fields", f.attribute;
+                note = first.span => "First mapped here"
+            );
+        }
+    }
+
+    // Only one field may collect the remaining attributes
+    if fields
+        .iter()
+        .filter(|f| matches!(f._type, FieldType::Rest))
+        .count()
+        > 1
+    {
+        emit_error!(struct_.fields, "Only one field can be marked with `rest`");
+    }
+
+    // Flattened fields add their own attributes to the parent's lists
+    let flattened_types = fields
+        .iter()
+        .filter(|f| matches!(f._type, FieldType::Flatten))
+        .map(|f| &f.ty)
+        .collect::<Vec<_>>();
+
+    // The filters of the flattened types must also hold, so they are combined with the struct's own
+    let filter = |filter: &Option<LitStr>, method: Ident, operator: &str| {
+        let own = match filter {
+            Some(f) => quote!(Some(#f.to_string())),
+            None => quote!(None),
+        };
+        quote! {
+            let filters = [#own #(, <#flattened_types as ::tequila::FromTequilaAttributes>::#method())*]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<String>>();
+            match filters.len() {
+                0 => None,
+                1 => filters.into_iter().next(),
+                _ => Some(filters.iter().map(|f| format!("({f})")).collect::<Vec<_>>().join(#operator)),
+            }
+        }
+    };
+    let require = filter(&require, Ident::new("require", Span::call_site()), "&");
+    let allow = filter(&allow, Ident::new("allow", Span::call_site()), "|");
+
+    let service = match service {
+        Some(s) => quote!(Some(#s.into())),
+        None => quote!(None),
+    };
+    let language = match language {
+        Some(l) => quote!(Some(#l.into())),
+        None => quote!(None),
+    };
+    let authstrength = match authstrength {
+        Some(a) => quote!(Some(#a)),
+        None => quote!(None),
+    };
+
     // In order to check all fields before returning an error (thus indicating all missing fields in the error message),
     // we store all values in temporary variables, and the list of missing fields in the Vec missing
     // Temp variables are named f{field_number}, we cannot use their names since they may be anonym
     let field_variables = fields.iter().enumerate().map(|(i, f)| {
-        let Field { attribute: key, _type ,..} = f;
+        let Field { attribute: key, _type, ty, ..} = f;
         let name = Ident::new(&format!("f{i}"), Span::call_site());
-        let key = Ident::new(&key, Span::call_site());
-        let key_str = key.to_string();
+        let key_str = key.as_str();
 
         match _type {
             FieldType::Other => quote!{
-                let #name = attributes.get(&#key_str.to_string()).cloned();
-                if #name.is_none() { missing.push(#key_str.to_string()) }
+                let #name: Option<#ty> = match attributes.get(#key_str) {
+                    Some(v) => match v.parse() {
+                        Ok(v) => Some(v),
+                        Err(_) => {
+                            invalid.push(#key_str.to_string());
+                            None
+                        }
+                    },
+                    None => {
+                        missing.push(#key_str.to_string());
+                        None
+                    }
+                };
             },
             FieldType::Option => quote!{
-                let #name = attributes.get(&#key_str.to_string()).unwrap_or_default();
+                let #name: #ty = match attributes.get(#key_str).map(|v| v.parse()) {
+                    Some(Ok(v)) => Some(v),
+                    Some(Err(_)) => {
+                        invalid.push(#key_str.to_string());
+                        None
+                    }
+                    None => None,
+                };
             },
             FieldType::Vec => quote! {
-                let #name = attributes.get(&#key_str.to_string()).unwrap_or_default().split(',').collect();
+                let #name: #ty = attributes
+                    .get_all(#key_str)
+                    .flat_map(|v| v.split(','))
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .filter_map(|s| match s.parse() {
+                        Ok(v) => Some(v),
+                        Err(_) => {
+                            invalid.push(#key_str.to_string());
+                            None
+                        }
+                    })
+                    .collect();
+            },
+            FieldType::Collect => quote! {
+                let #name: #ty = attributes
+                    .get_all(#key_str)
+                    .flat_map(|v| v.split(','))
+                    .map(|s| s.trim())
+                    .filter(|s| !s.is_empty())
+                    .map(::std::string::String::from)
+                    .collect();
+            },
+            FieldType::Flatten => quote! {
+                let #name = match <#ty as ::tequila::FromTequilaAttributes>::from_flattened_attributes(attributes.clone(), &known) {
+                    Ok(v) => Some(v),
+                    Err(::tequila::TequilaError::MissingAttributes(m)) => {
+                        missing.extend(m);
+                        None
+                    }
+                    Err(::tequila::TequilaError::InvalidAttributes(m)) => {
+                        invalid.extend(m);
+                        None
+                    }
+                    Err(e) => return Err(e),
+                };
+            },
+            FieldType::Rest => quote! {
+                let #name = attributes
+                    .iter()
+                    .filter(|(k, _)| unknown.iter().any(|u| u == k))
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect();
             },
         }
     })
@@ -243,7 +661,7 @@ pub fn derive_from_tequila_attributes(ts: TokenStream) -> TokenStream {
             let name = Ident::new(&format!("f{i}"), Span::call_site());
 
             let value = match f._type {
-                FieldType::Other => quote!(#name.unwrap()),
+                FieldType::Other | FieldType::Flatten => quote!(#name.unwrap()),
                 _ => quote!(#name),
             };
 
@@ -266,26 +684,230 @@ pub fn derive_from_tequila_attributes(ts: TokenStream) -> TokenStream {
         None => quote!(Self),
     };
 
+    // The attributes used by this type, or by the types it is flattened into, are passed down to the flattened fields
+    let uses_known = deny_unknown
+        || fields
+            .iter()
+            .any(|f| matches!(f._type, FieldType::Rest | FieldType::Flatten));
+    let known_attributes = if uses_known {
+        quote! {
+            let known = [
+                <Self as ::tequila::FromTequilaAttributes>::requested_attributes(),
+                <Self as ::tequila::FromTequilaAttributes>::wished_attributes(),
+                parent_known.to_vec(),
+            ]
+            .concat();
+        }
+    } else {
+        quote!(let _ = parent_known;)
+    };
+
+    // The attributes which are not known (nor part of the protocol) are only computed when needed
+    let uses_unknown = deny_unknown || fields.iter().any(|f| matches!(f._type, FieldType::Rest));
+    let unknown_attributes = if uses_unknown {
+        quote! {
+            let mut unknown = attributes
+                .keys()
+                .filter(|k| !known.iter().any(|a| a == k) && !::tequila::PROTOCOL_KEYS.contains(k))
+                .map(String::from)
+                .collect::<::std::vec::Vec<_>>();
+            unknown.sort();
+        }
+    } else {
+        quote!()
+    };
+    let deny_unknown = if deny_unknown {
+        quote! {
+            if !unknown.is_empty() {
+                return Err(::tequila::TequilaError::UnknownAttributes(unknown));
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    // The values of the fields depending on type parameters must be parsable, and the flattened fields convertible
+    let mut generics = struct_.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for f in &fields {
+        let ty = match f._type {
+            FieldType::Option | FieldType::Vec => generic_argument(&f.ty).unwrap_or(&f.ty),
+            _ => &f.ty,
+        };
+        if !uses_type_params(ty, &struct_.generics) {
+            continue;
+        }
+        match f._type {
+            FieldType::Other | FieldType::Option | FieldType::Vec => where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::std::str::FromStr)),
+            FieldType::Collect => where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::std::iter::FromIterator<::std::string::String>)),
+            FieldType::Flatten => where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::tequila::FromTequilaAttributes)),
+            FieldType::Rest => {}
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Newtypes are also parsable from a single value, so that they can be used as field types
+    let from_str = match (&struct_.fields, fields.as_slice()) {
+        (
+            syn::Fields::Unnamed(_),
+            [Field {
+                ty,
+                _type: FieldType::Other,
+                ..
+            }],
+        ) => quote! {
+            impl #impl_generics ::std::str::FromStr for #id #ty_generics #where_clause {
+                type Err = <#ty as ::std::str::FromStr>::Err;
+
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    s.parse().map(Self)
+                }
+            }
+        },
+        _ => quote!(),
+    };
+
+    // Structures with sensitive fields implement `Debug`, hiding their values
+    let debug = if fields.iter().any(|f| f.sensitive) {
+        let mut generics = struct_.generics.clone();
+        let where_clause = generics.make_where_clause();
+        for f in fields
+            .iter()
+            .filter(|f| !f.sensitive && uses_type_params(&f.ty, &struct_.generics))
+        {
+            let ty = &f.ty;
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::std::fmt::Debug));
+        }
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+        let values = fields.iter().map(|f| {
+            let member = &f.member;
+            if f.sensitive {
+                quote!(&::tequila::Redacted::new(()))
+            } else {
+                quote!(&self.#member)
+            }
+        });
+        let debug_fields = match &struct_.fields {
+            syn::Fields::Named(_) => {
+                let names = fields.iter().map(|f| f.name.as_deref().unwrap_or_default());
+                quote!(f.debug_struct(stringify!(#id)) #(.field(#names, #values))* .finish())
+            }
+            _ => quote!(f.debug_tuple(stringify!(#id)) #(.field(#values))* .finish()),
+        };
+
+        quote! {
+            impl #impl_generics ::std::fmt::Debug for #id #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                    #debug_fields
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     // Constructs the trait implementation
     quote! {
-        impl ::tequila::FromTequilaAttributes for #id {
-            fn from_tequila_attributes(attributes: ::std::collections::HashMap<String, String>) -> Result<Self, ::tequila::TequilaError> {
+        impl #impl_generics ::tequila::FromTequilaAttributes for #id #ty_generics #where_clause {
+            fn from_tequila_attributes(attributes: ::tequila::AttributeMap) -> Result<Self, ::tequila::TequilaError> {
+                <Self as ::tequila::FromTequilaAttributes>::from_flattened_attributes(attributes, &[])
+            }
+
+            fn from_flattened_attributes(
+                attributes: ::tequila::AttributeMap,
+                parent_known: &[::std::string::String],
+            ) -> Result<Self, ::tequila::TequilaError> {
                 let mut missing: ::std::vec::Vec<::std::string::String> = vec![];
+                let mut invalid: ::std::vec::Vec<::std::string::String> = vec![];
+                #known_attributes
+                #unknown_attributes
                 #field_variables
-                if missing.is_empty() {
-                    Ok(#instanciation)
-                } else {
+                if !missing.is_empty() {
                     Err(::tequila::TequilaError::MissingAttributes(missing))
+                } else if !invalid.is_empty() {
+                    invalid.dedup();
+                    Err(::tequila::TequilaError::InvalidAttributes(invalid))
+                } else {
+                    #deny_unknown
+                    Ok(#instanciation)
                 }
             }
 
             fn wished_attributes() -> Vec<String> {
-                vec![#wished_attributes]
+                let mut attributes: Vec<String> = vec![#wished_attributes];
+                #(attributes.extend(<#flattened_types as ::tequila::FromTequilaAttributes>::wished_attributes());)*
+                attributes
             }
 
             fn requested_attributes() -> Vec<String> {
-                vec![#requested_attributes]
+                let mut attributes: Vec<String> = vec![#requested_attributes];
+                #(attributes.extend(<#flattened_types as ::tequila::FromTequilaAttributes>::requested_attributes());)*
+                attributes
+            }
+
+            fn require() -> Option<String> {
+                #require
+            }
+
+            fn allow() -> Option<String> {
+                #allow
+            }
+
+            fn service() -> Option<String> {
+                #service
+            }
+
+            fn language() -> Option<String> {
+                #language
+            }
+
+            fn authstrength() -> Option<u32> {
+                #authstrength
             }
         }
+
+        #from_str
+
+        #debug
     }.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_each_operation_once() {
+        assert_eq!(edit_distance("email", "email"), 0);
+        assert_eq!(edit_distance("emai", "email"), 1);
+        assert_eq!(edit_distance("emaill", "email"), 1);
+        assert_eq!(edit_distance("enail", "email"), 1);
+        assert_eq!(edit_distance("", "name"), 4);
+    }
+
+    #[test]
+    fn edit_distance_counts_transpositions_as_one() {
+        assert_eq!(edit_distance("nmae", "name"), 1);
+        assert_eq!(edit_distance("firstnmae", "firstname"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+    }
+
+    #[test]
+    fn only_close_attributes_are_typos() {
+        assert!(is_typo("emial", edit_distance("emial", "email")));
+        assert!(is_typo("uid", 1));
+        assert!(!is_typo("uid", 2));
+        assert!(is_typo("firstnmae", 3));
+        assert!(!is_typo("firstnmae", 4));
+        assert!(!is_typo("phone", edit_distance("phone", "name")));
+    }
+}
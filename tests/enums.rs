@@ -0,0 +1,42 @@
+use tequila::{AttributeMap, FromTequilaAttributes};
+
+#[derive(FromTequilaAttributes)]
+#[tequila(no_check, require = "email")]
+struct Student {
+    email: String,
+    unit: Option<String>,
+}
+
+#[derive(FromTequilaAttributes)]
+#[tequila(no_check, tag = "categorie")]
+enum Who {
+    Student(Student),
+    Guest,
+}
+
+#[test]
+fn tagged_enum_only_requests_the_tag() {
+    assert_eq!(Who::requested_attributes(), ["categorie"]);
+    assert_eq!(Who::wished_attributes(), ["unit", "email"]);
+}
+
+#[test]
+fn tagged_enum_filters_apply_to_their_variant() {
+    assert_eq!(
+        Who::require().as_deref(),
+        Some("(categorie=Student&(email))|categorie=Guest")
+    );
+    assert_eq!(Who::allow(), None);
+}
+
+#[test]
+fn tagged_enum_selects_its_variant() {
+    assert!(matches!(
+        Who::from_tequila_attributes(AttributeMap::from_iter([("categorie", "Guest")])),
+        Ok(Who::Guest)
+    ));
+    assert!(matches!(
+        Who::from_tequila_attributes(AttributeMap::from_iter([("categorie", "Student"), ("email", "e")])),
+        Ok(Who::Student(Student { email, unit: None })) if email == "e"
+    ));
+}
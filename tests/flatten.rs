@@ -0,0 +1,104 @@
+use tequila::{AttributeMap, FromTequilaAttributes, TequilaError};
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct Contact {
+    email: String,
+    phone: Option<String>,
+}
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct User {
+    name: String,
+    unit: Vec<String>,
+    #[tequila(flatten)]
+    contact: Contact,
+}
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check, deny_unknown)]
+struct Strict {
+    email: String,
+}
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct StrictParent {
+    name: String,
+    #[tequila(flatten)]
+    child: Strict,
+}
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct Rest {
+    email: String,
+    #[tequila(rest)]
+    rest: AttributeMap,
+}
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct RestParent {
+    name: String,
+    #[tequila(flatten)]
+    child: Rest,
+}
+
+#[test]
+fn flattened_attributes_are_merged_into_the_parent() {
+    assert_eq!(User::requested_attributes(), ["name", "email"]);
+    assert_eq!(User::wished_attributes(), ["unit", "phone"]);
+}
+
+#[test]
+fn flattened_fields_are_built_from_the_same_attributes() {
+    let user = User::from_tequila_attributes(AttributeMap::from_iter([
+        ("name", "n"),
+        ("email", "e"),
+        ("phone", "p"),
+    ]))
+    .unwrap();
+    assert_eq!(user.name, "n");
+    assert!(user.unit.is_empty());
+    assert_eq!(user.contact.email, "e");
+    assert_eq!(user.contact.phone.as_deref(), Some("p"));
+}
+
+#[test]
+fn flattened_missing_attributes_are_reported_with_the_parent_ones() {
+    assert!(matches!(
+        User::from_tequila_attributes(AttributeMap::from_iter([("phone", "p")])),
+        Err(TequilaError::MissingAttributes(m)) if m == ["name", "email"]
+    ));
+}
+
+#[test]
+fn flattened_deny_unknown_accepts_parent_attributes() {
+    let parent = StrictParent::from_tequila_attributes(AttributeMap::from_iter([
+        ("name", "n"),
+        ("email", "e"),
+    ]))
+    .unwrap();
+    assert_eq!(parent.name, "n");
+    assert_eq!(parent.child.email, "e");
+
+    assert!(matches!(
+        StrictParent::from_tequila_attributes(AttributeMap::from_iter([("name", "n"), ("email", "e"), ("x", "y")])),
+        Err(TequilaError::UnknownAttributes(u)) if u == ["x"]
+    ));
+}
+
+#[test]
+fn flattened_rest_excludes_parent_attributes() {
+    let parent = RestParent::from_tequila_attributes(AttributeMap::from_iter([
+        ("name", "n"),
+        ("email", "e"),
+        ("x", "y"),
+    ]))
+    .unwrap();
+    assert_eq!(parent.name, "n");
+    assert_eq!(parent.child.email, "e");
+    assert_eq!(parent.child.rest, AttributeMap::from_iter([("x", "y")]));
+}
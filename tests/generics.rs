@@ -0,0 +1,59 @@
+use tequila::{AttributeMap, FromTequilaAttributes, TequilaError};
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct Contact {
+    email: String,
+}
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct Generic<T, F>
+where
+    T: Clone,
+{
+    #[tequila("uniqueid")]
+    id: T,
+    unit: Vec<T>,
+    phone: Option<T>,
+    #[tequila(flatten)]
+    inner: F,
+}
+
+#[test]
+fn generic_fields_are_parsed_with_their_type() {
+    let generic = Generic::<u32, Contact>::from_tequila_attributes(AttributeMap::from_iter([
+        ("uniqueid", "123456"),
+        ("unit", "1,2"),
+        ("email", "e"),
+    ]))
+    .unwrap();
+
+    assert_eq!(generic.id, 123456);
+    assert_eq!(generic.unit, [1, 2]);
+    assert_eq!(generic.phone, None);
+    assert_eq!(generic.inner.email, "e");
+}
+
+#[test]
+fn generic_flattened_lists_are_merged() {
+    assert_eq!(
+        Generic::<String, Contact>::requested_attributes(),
+        ["uniqueid", "email"]
+    );
+    assert_eq!(
+        Generic::<String, Contact>::wished_attributes(),
+        ["unit", "phone"]
+    );
+}
+
+#[test]
+fn generic_fields_report_invalid_values() {
+    assert!(matches!(
+        Generic::<u32, Contact>::from_tequila_attributes(AttributeMap::from_iter([
+            ("uniqueid", "x"),
+            ("email", "e"),
+        ])),
+        Err(TequilaError::InvalidAttributes(i)) if i == ["uniqueid"]
+    ));
+}
@@ -0,0 +1,117 @@
+use tequila::{
+    attributes::Groups, AttributeMap, FromTequilaAttributes, IntoTequilaAttributes, Redacted,
+};
+
+#[derive(FromTequilaAttributes, IntoTequilaAttributes, Debug, PartialEq)]
+#[tequila(no_check)]
+struct Attributes {
+    name: String,
+    email: Option<String>,
+    group: Vec<String>,
+}
+
+#[test]
+fn round_trips() {
+    let attributes = Attributes {
+        name: "n".into(),
+        email: None,
+        group: vec!["a".into(), "b".into()],
+    };
+
+    let map = attributes.into_tequila_attributes();
+    assert_eq!(map.get_all("group").collect::<Vec<_>>(), ["a,b"]);
+    assert_eq!(
+        Attributes::from_tequila_attributes(map).unwrap(),
+        Attributes {
+            name: "n".into(),
+            email: None,
+            group: vec!["a".into(), "b".into()],
+        }
+    );
+}
+
+#[test]
+fn empty_vec_is_skipped() {
+    let map = Attributes {
+        name: "n".into(),
+        email: None,
+        group: vec![],
+    }
+    .into_tequila_attributes();
+
+    assert!(!map.contains_key("group"));
+}
+
+#[test]
+fn vec_values_are_split_on_commas() {
+    let map = Attributes {
+        name: "n".into(),
+        email: Some("e".into()),
+        group: vec!["a,b".into(), "".into()],
+    }
+    .into_tequila_attributes();
+
+    assert_eq!(
+        Attributes::from_tequila_attributes(map).unwrap().group,
+        ["a", "b"]
+    );
+}
+
+#[derive(FromTequilaAttributes, IntoTequilaAttributes, Debug, PartialEq)]
+#[tequila(no_check)]
+struct Sensitive {
+    #[tequila("uniqueid")]
+    sciper: Redacted<u32>,
+    email: Option<Redacted<String>>,
+}
+
+#[test]
+fn redacted_values_round_trip() {
+    let sensitive = Sensitive {
+        sciper: Redacted::new(123456),
+        email: Some(Redacted::new("e".into())),
+    };
+    assert_eq!(
+        format!("{sensitive:?}"),
+        "Sensitive { sciper: <redacted>, email: Some(<redacted>) }"
+    );
+
+    let map = sensitive.into_tequila_attributes();
+    assert_eq!(map.get("uniqueid"), Some("123456"));
+    assert_eq!(
+        Sensitive::from_tequila_attributes(map).unwrap(),
+        Sensitive {
+            sciper: Redacted::new(123456),
+            email: Some(Redacted::new("e".into())),
+        }
+    );
+}
+
+#[derive(FromTequilaAttributes, IntoTequilaAttributes, Debug, PartialEq)]
+#[tequila(no_check)]
+struct Collected {
+    #[tequila(collect)]
+    group: Groups,
+}
+
+#[test]
+fn collect_fields_round_trip() {
+    assert!(Collected::requested_attributes().is_empty());
+    assert_eq!(Collected::wished_attributes(), ["group"]);
+
+    let collected = Collected::from_tequila_attributes(AttributeMap::from_iter([
+        ("group", "b,a"),
+        ("group", "c"),
+    ]))
+    .unwrap();
+    assert_eq!(collected.group.iter().collect::<Vec<_>>(), ["a", "b", "c"]);
+
+    let map = collected.into_tequila_attributes();
+    assert_eq!(map.get_all("group").collect::<Vec<_>>(), ["a,b,c"]);
+    assert_eq!(
+        Collected::from_tequila_attributes(AttributeMap::new()).unwrap(),
+        Collected {
+            group: Groups::default()
+        }
+    );
+}
@@ -0,0 +1,76 @@
+use tequila::{attributes::Person, roles::RoleMapper, AttributeMap, FromTequilaAttributes};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Role {
+    Admin,
+    Staff,
+}
+
+fn person(pairs: &[(&str, &str)]) -> Person {
+    let attributes: AttributeMap = [
+        ("uniqueid", "123456"),
+        ("name", "Doe"),
+        ("firstname", "Jane"),
+    ]
+    .iter()
+    .chain(pairs)
+    .copied()
+    .collect();
+    Person::from_tequila_attributes(attributes).unwrap()
+}
+
+#[test]
+fn person_groups_are_parsed() {
+    let person = person(&[("group", "myapp-admins,other")]);
+
+    assert!(person.group.contains("myapp-admins"));
+    assert!(person.group.contains("other"));
+    assert_eq!(person.group.len(), 2);
+}
+
+#[test]
+fn person_groups_are_collected_from_every_value() {
+    let person = person(&[("group", "a,b"), ("group", "c")]);
+
+    assert_eq!(person.group.iter().collect::<Vec<_>>(), ["a", "b", "c"]);
+}
+
+#[test]
+fn person_groups_are_trimmed() {
+    let mapper = RoleMapper::new().group("myapp-admins", Role::Admin);
+    let person = person(&[("group", "other, myapp-admins")]);
+
+    assert_eq!(person.group, "other,myapp-admins".parse().unwrap());
+    assert_eq!(mapper.roles_of(&person), [Role::Admin]);
+}
+
+#[test]
+fn person_groups_are_optional() {
+    assert!(!Person::requested_attributes().contains(&"group".to_string()));
+    assert!(Person::wished_attributes().contains(&"group".to_string()));
+
+    let person = person(&[]);
+    assert!(person.group.is_empty());
+}
+
+#[test]
+fn roles_of_person_uses_groups() {
+    let mapper = RoleMapper::new()
+        .group("myapp-admins", Role::Admin)
+        .group("myapp-staff", Role::Staff);
+    let person = person(&[("group", "myapp-admins,other")]);
+
+    assert_eq!(mapper.roles_of(&person), [Role::Admin]);
+    assert!(!mapper.person_has_role(&person, &Role::Staff));
+}
+
+#[test]
+fn unit_rules_match_subunits() {
+    let mapper = RoleMapper::new().unit("ENAC", Role::Staff);
+
+    let inside = person(&[("where", "LASIG/IIE/ENAC/EPFL")]);
+    let outside = person(&[("where", "IC-IT/IC/EPFL")]);
+
+    assert!(mapper.person_has_role(&inside, &Role::Staff));
+    assert!(!mapper.person_has_role(&outside, &Role::Staff));
+}
@@ -0,0 +1,23 @@
+use tequila::FromTequilaAttributes;
+
+#[derive(FromTequilaAttributes)]
+#[tequila(no_check, service = "My app", language = "fr", authstrength = 2)]
+struct Bound;
+
+#[derive(FromTequilaAttributes)]
+#[tequila(no_check)]
+struct Unbound;
+
+#[test]
+fn parameters_are_bound_to_the_type() {
+    assert_eq!(Bound::service().as_deref(), Some("My app"));
+    assert_eq!(Bound::language().as_deref(), Some("fr"));
+    assert_eq!(Bound::authstrength(), Some(2));
+}
+
+#[test]
+fn parameters_default_to_none() {
+    assert_eq!(Unbound::service(), None);
+    assert_eq!(Unbound::language(), None);
+    assert_eq!(Unbound::authstrength(), None);
+}
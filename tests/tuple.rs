@@ -0,0 +1,48 @@
+use tequila::{AttributeMap, FromTequilaAttributes};
+
+#[derive(FromTequilaAttributes, Debug, PartialEq)]
+#[tequila(no_check)]
+struct Uid(#[tequila("uniqueid")] u32);
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct Named(#[tequila("name")] String, #[tequila("uniqueid")] Uid);
+
+#[derive(FromTequilaAttributes, Debug)]
+#[tequila(no_check)]
+struct User {
+    #[tequila("uniqueid")]
+    id: Uid,
+    manager: Option<Uid>,
+}
+
+#[test]
+fn newtype_is_parsed_from_a_value() {
+    assert_eq!("123456".parse::<Uid>().unwrap(), Uid(123456));
+    assert!("x".parse::<Uid>().is_err());
+}
+
+#[test]
+fn tuple_struct_uses_the_explicit_keys() {
+    assert_eq!(Named::requested_attributes(), ["name", "uniqueid"]);
+
+    let named = Named::from_tequila_attributes(AttributeMap::from_iter([
+        ("uniqueid", "123456"),
+        ("name", "n"),
+    ]))
+    .unwrap();
+    assert_eq!(named.0, "n");
+    assert_eq!(named.1, Uid(123456));
+}
+
+#[test]
+fn newtype_is_usable_as_a_field_type() {
+    let user = User::from_tequila_attributes(AttributeMap::from_iter([
+        ("uniqueid", "123456"),
+        ("manager", "654321"),
+    ]))
+    .unwrap();
+
+    assert_eq!(user.id, Uid(123456));
+    assert_eq!(user.manager, Some(Uid(654321)));
+}